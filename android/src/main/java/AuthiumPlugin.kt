@@ -0,0 +1,74 @@
+package com.ezerium.authium
+
+import android.app.Activity
+import android.content.ActivityNotFoundException
+import android.content.Intent
+import android.net.Uri
+import androidx.browser.customtabs.CustomTabsIntent
+import app.tauri.annotation.Command
+import app.tauri.annotation.InvokeArg
+import app.tauri.annotation.TauriPlugin
+import app.tauri.plugin.Invoke
+import app.tauri.plugin.JSObject
+import app.tauri.plugin.Plugin
+
+@InvokeArg
+class SignInArgs {
+    lateinit var url: String
+    lateinit var callbackScheme: String
+}
+
+/**
+ * Opens the Authium sign-in page in a Custom Tab. Authium redirects to
+ * `<callbackScheme>://authium/callback`, which the app's activity must accept with an
+ * intent filter; the redirect arrives here through [onNewIntent].
+ */
+@TauriPlugin
+class AuthiumPlugin(private val activity: Activity) : Plugin(activity) {
+    private var pending: Invoke? = null
+    private var callbackScheme: String? = null
+    private var tabOpen = false
+
+    @Command
+    fun signIn(invoke: Invoke) {
+        val args = invoke.parseArgs(SignInArgs::class.java)
+
+        pending?.reject("Sign-in was replaced by a new one", "cancelled")
+        pending = invoke
+        callbackScheme = args.callbackScheme
+        tabOpen = false
+
+        try {
+            CustomTabsIntent.Builder().build().launchUrl(activity, Uri.parse(args.url))
+        } catch (e: ActivityNotFoundException) {
+            finish()?.reject("No browser available to sign in", "unavailable")
+        }
+    }
+
+    override fun onNewIntent(intent: Intent) {
+        val uri = intent.data ?: return
+        if (uri.scheme != callbackScheme) return
+
+        finish()?.resolve(JSObject().apply { put("url", uri.toString()) })
+    }
+
+    override fun onPause() {
+        if (pending != null) tabOpen = true
+    }
+
+    override fun onResume() {
+        // the redirect is delivered before the activity resumes, so a sign-in that is still
+        // pending here was ended by closing the tab
+        if (tabOpen) {
+            finish()?.reject("Sign-in was cancelled", "cancelled")
+        }
+    }
+
+    private fun finish(): Invoke? {
+        val invoke = pending
+        pending = null
+        callbackScheme = null
+        tabOpen = false
+        return invoke
+    }
+}
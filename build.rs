@@ -1,4 +1,4 @@
-const COMMANDS: &[&str] = &["sign_in", "logout", "is_logged_in", "get_user", "refresh"];
+const COMMANDS: &[&str] = &["sign_in", "sign_in_device", "logout", "is_logged_in", "get_auth_state", "get_access_token", "get_user", "refresh", "reauthenticate", "update_user", "update_avatar", "get_session_info", "start_totp_enrollment", "confirm_totp_enrollment"];
 
 fn main() {
   tauri_plugin::Builder::new(COMMANDS)
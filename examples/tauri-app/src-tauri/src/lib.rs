@@ -1,8 +1,3 @@
-#[macro_use]
-extern crate dotenv_codegen;
-
-use tauri_plugin_authium::AuthiumConfig;
-
 // Learn more about Tauri commands at https://v2.tauri.app/develop/calling-rust/#commands
 #[tauri::command]
 fn greet(name: &str) -> String {
@@ -11,15 +6,14 @@ fn greet(name: &str) -> String {
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
+    // AUTHIUM_API_KEY, AUTHIUM_APP_ID and AUTHIUM_PROFILE are read from the environment at
+    // startup, so the same build can be pointed at another Authium environment
     dotenv::dotenv().ok();
     tauri::Builder::default()
         .invoke_handler(tauri::generate_handler![
             greet
         ])
-        .plugin(tauri_plugin_authium::init(Some(AuthiumConfig::new(
-            dotenv!("API_KEY").into(),
-            dotenv!("APP_ID").into(),
-        ))))
+        .plugin(tauri_plugin_authium::Builder::new().build())
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
 }
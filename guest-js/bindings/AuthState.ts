@@ -0,0 +1,7 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { User } from "./User";
+
+/**
+ * Whether a user is signed in, and who. Subscribers are notified whenever it changes.
+ */
+export type AuthState = { "status": "signedOut" } | { "status": "signedIn", "user": User };
@@ -0,0 +1,18 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * User code of a pending device authorization, to be shown on screen.
+ */
+export type DeviceCode = { userCode: string, verificationUri: string, 
+/**
+ * Verification URL with the user code already filled in, e.g. for a QR code.
+ */
+verificationUriComplete: string | null, 
+/**
+ * Seconds until the user code expires.
+ */
+expiresIn: number, 
+/**
+ * Seconds between polls of the token endpoint.
+ */
+interval: number, };
@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * A kind of second factor.
+ */
+export type MfaFactor = "totp" | "webauthn" | "sms" | "other";
@@ -0,0 +1,7 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type ReauthenticatePayload = { 
+/**
+ * Seconds since the last sign-in after which the user has to sign in again.
+ */
+maxAge: number, };
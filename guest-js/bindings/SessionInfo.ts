@@ -0,0 +1,18 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Details about the current session. Timestamps are seconds since the Unix epoch.
+ */
+export type SessionInfo = { loggedIn: boolean, expiresAt: number | null, issuedAt: number | null, 
+/**
+ * Unix time at which the user last signed in, as opposed to refreshing the session.
+ */
+authTime: number | null, scopes: Array<string>, tokenType: string | null, 
+/**
+ * Whether the session was restored from disk on launch.
+ */
+restored: boolean, 
+/**
+ * Result of the server-side introspection, `None` if it was not requested.
+ */
+active: boolean | null, };
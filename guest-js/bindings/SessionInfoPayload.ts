@@ -0,0 +1,7 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type SessionInfoPayload = { 
+/**
+ * Asks Authium whether the token is still active instead of relying on local state only.
+ */
+introspect: boolean, };
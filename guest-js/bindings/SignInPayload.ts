@@ -0,0 +1,12 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type SignInPayload = { 
+/**
+ * Requested session lifetime in seconds.
+ */
+expiry: number | null, 
+/**
+ * Authentication context class to request, e.g. `mfa` to require a second factor.
+ * Defaults to `acrValues` in the configuration. Not supported by the device flow.
+ */
+acr?: string, };
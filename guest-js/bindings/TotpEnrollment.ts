@@ -0,0 +1,15 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * A started TOTP enrollment. The user adds it to their authenticator app and confirms it with
+ * a code, see [`ConfirmTotpPayload`].
+ */
+export type TotpEnrollment = { enrollmentId: string, 
+/**
+ * `otpauth://` URI to show as a QR code.
+ */
+otpauthUri: string, 
+/**
+ * The shared secret, for entering it by hand.
+ */
+secret: string, };
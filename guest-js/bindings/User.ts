@@ -0,0 +1,12 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { MfaFactor } from "./MfaFactor";
+
+export type User = { id: number, username: string | null, email: string | null, avatar: string | null, first_name: string | null, last_name: string | null, phone: string | null, language: string | null, timezone: string | null, region: string | null, 
+/**
+ * Whether the user has to confirm sign-ins with a second factor.
+ */
+mfa_enabled: boolean, 
+/**
+ * Second factors the user has enrolled.
+ */
+mfa_factors: Array<MfaFactor>, };
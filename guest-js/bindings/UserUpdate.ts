@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Editable fields of the signed-in [`User`]. Fields left as `None` are not changed.
+ */
+export type UserUpdate = { first_name?: string, last_name?: string, phone?: string, language?: string, timezone?: string, region?: string, };
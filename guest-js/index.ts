@@ -1,5 +1,18 @@
 import { invoke } from '@tauri-apps/api/core'
-import { User } from './types';
+import {
+    AuthState,
+    ConfirmTotpPayload,
+    DeviceCode,
+    LogoutPayload,
+    ReauthenticatePayload,
+    RefreshPayload,
+    SessionInfo,
+    SessionInfoPayload,
+    SignInPayload,
+    TotpEnrollment,
+    User,
+    UserUpdate,
+} from './types';
 
 export async function getUser(): Promise<User | null> {
     return await invoke<User | null>('plugin:authium|get_user');
@@ -9,16 +22,65 @@ export async function isLoggedIn(): Promise<boolean> {
     return await invoke<boolean>('plugin:authium|is_logged_in');
 }
 
-export function signIn(expiry: number | null = 30 * 24 * 60 * 60): void {
-    invoke('plugin:authium|sign_in', expiry == null ? undefined : { expiry });
+export async function getAuthState(): Promise<AuthState> {
+    return await invoke<AuthState>('plugin:authium|get_auth_state');
 }
 
-export async function logout(): Promise<void> {
-    await invoke('plugin:authium|logout');
+/** A valid access token for calling other APIs on behalf of the user. Requires the `authium:allow-get-access-token` permission. */
+export async function getAccessToken(): Promise<string> {
+    return await invoke<string>('plugin:authium|get_access_token');
 }
 
-export function refresh(refresh_data: boolean = false): void {
-    invoke('plugin:authium|refresh', { refresh_data });
+/** Opens the sign-in page. `acr` requests an authentication context class, e.g. `mfa`. */
+export async function signIn(expiry: number | null = 30 * 24 * 60 * 60, acr?: string): Promise<void> {
+    const payload: SignInPayload = { expiry, acr };
+    await invoke('plugin:authium|sign_in', { payload });
 }
 
-export * from './types';
\ No newline at end of file
+export async function signInWithDevice(expiry: number | null = 30 * 24 * 60 * 60): Promise<DeviceCode> {
+    const payload: SignInPayload = { expiry };
+    return await invoke<DeviceCode>('plugin:authium|sign_in_device', { payload });
+}
+
+export async function logout(everywhere: boolean = false): Promise<void> {
+    const payload: LogoutPayload = { everywhere };
+    await invoke('plugin:authium|logout', { payload });
+}
+
+export async function refresh(refresh_data: boolean = false): Promise<void> {
+    const payload: RefreshPayload = { refreshData: refresh_data };
+    await invoke('plugin:authium|refresh', { payload });
+}
+
+/** Asks the user to sign in again unless they did within `maxAge` seconds. Rejects if they cancel. */
+export async function reauthenticate(maxAge: number): Promise<void> {
+    const payload: ReauthenticatePayload = { maxAge };
+    await invoke('plugin:authium|reauthenticate', { payload });
+}
+
+export async function updateUser(update: UserUpdate): Promise<User> {
+    return await invoke<User>('plugin:authium|update_user', { payload: update });
+}
+
+/** Uploads `data`, an image of type `mimeType`, as the avatar of the signed-in user. */
+export async function updateAvatar(data: Uint8Array, mimeType: string): Promise<User> {
+    return await invoke<User>('plugin:authium|update_avatar', data, { headers: { 'Authium-Mime-Type': mimeType } });
+}
+
+export async function getSessionInfo(introspect: boolean = false): Promise<SessionInfo> {
+    const payload: SessionInfoPayload = { introspect };
+    return await invoke<SessionInfo>('plugin:authium|get_session_info', { payload });
+}
+
+/** Starts adding an authenticator app as a second factor. Show `otpauthUri` as a QR code. */
+export async function startTotpEnrollment(): Promise<TotpEnrollment> {
+    return await invoke<TotpEnrollment>('plugin:authium|start_totp_enrollment');
+}
+
+/** Confirms a TOTP enrollment with a code from the authenticator app. */
+export async function confirmTotpEnrollment(enrollmentId: string, code: string): Promise<User> {
+    const payload: ConfirmTotpPayload = { enrollmentId, code };
+    return await invoke<User>('plugin:authium|confirm_totp_enrollment', { payload });
+}
+
+export * from './types';
@@ -1,14 +1,13 @@
-export interface User {
-    id: number;
-    username: string;
-    email: string;
-    phone: string;
-
-    first_name: string;
-    last_name: string;
-    avatar: string;
-    language: string;
-
-    created_at: string;
-    updated_at: string;
-}
\ No newline at end of file
+export type { User } from './bindings/User';
+export type { UserUpdate } from './bindings/UserUpdate';
+export type { SignInPayload } from './bindings/SignInPayload';
+export type { LogoutPayload } from './bindings/LogoutPayload';
+export type { RefreshPayload } from './bindings/RefreshPayload';
+export type { SessionInfoPayload } from './bindings/SessionInfoPayload';
+export type { SessionInfo } from './bindings/SessionInfo';
+export type { DeviceCode } from './bindings/DeviceCode';
+export type { AuthState } from './bindings/AuthState';
+export type { ReauthenticatePayload } from './bindings/ReauthenticatePayload';
+export type { MfaFactor } from './bindings/MfaFactor';
+export type { TotpEnrollment } from './bindings/TotpEnrollment';
+export type { ConfirmTotpPayload } from './bindings/ConfirmTotpPayload';
@@ -0,0 +1,59 @@
+import AuthenticationServices
+import SwiftRs
+import Tauri
+import UIKit
+
+class SignInArgs: Decodable {
+  let url: String
+  let callbackScheme: String
+}
+
+struct SignInResponse: Encodable {
+  let url: String
+}
+
+/// Runs the Authium sign-in page in an `ASWebAuthenticationSession`, which returns the
+/// redirect to `<callbackScheme>://authium/callback` without the app registering the scheme.
+class AuthiumPlugin: Plugin, ASWebAuthenticationPresentationContextProviding {
+  private var session: ASWebAuthenticationSession?
+
+  @objc public func signIn(_ invoke: Invoke) throws {
+    let args = try invoke.parseArgs(SignInArgs.self)
+    guard let url = URL(string: args.url) else {
+      invoke.reject("Invalid sign-in URL")
+      return
+    }
+
+    DispatchQueue.main.async {
+      self.session?.cancel()
+
+      let session = ASWebAuthenticationSession(url: url, callbackURLScheme: args.callbackScheme) {
+        [weak self] callbackURL, error in
+        self?.session = nil
+        if let callbackURL = callbackURL {
+          invoke.resolve(SignInResponse(url: callbackURL.absoluteString))
+        } else if let error = error as? ASWebAuthenticationSessionError, error.code == .canceledLogin {
+          invoke.reject("Sign-in was cancelled", code: "cancelled")
+        } else {
+          invoke.reject(error?.localizedDescription ?? "Sign-in failed")
+        }
+      }
+      session.presentationContextProvider = self
+      self.session = session
+
+      if !session.start() {
+        self.session = nil
+        invoke.reject("Could not start the sign-in session", code: "unavailable")
+      }
+    }
+  }
+
+  func presentationAnchor(for session: ASWebAuthenticationSession) -> ASPresentationAnchor {
+    return manager.viewController?.view.window ?? ASPresentationAnchor()
+  }
+}
+
+@_cdecl("init_plugin_authium")
+func initPlugin() -> Plugin {
+  return AuthiumPlugin()
+}
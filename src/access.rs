@@ -0,0 +1,58 @@
+use tauri::{ipc::Invoke, Manager, Runtime, Url, Webview};
+
+use crate::AuthiumConfig;
+
+/// Prefix of the labels of the windows that show the Authium sign-in page on desktop.
+pub(crate) const SIGN_IN_WINDOW: &str = "authium-auth-cb-signin";
+
+/// Wraps the plugin's invoke handler so that every command is checked against
+/// `allowedWindows` before it runs. Commands are rejected here even if a capability grants
+/// them, and the sign-in window, which shows a remote page, may not call any of them.
+pub(crate) fn guard<R: Runtime>(
+    handler: impl Fn(Invoke<R>) -> bool + Send + Sync + 'static,
+) -> impl Fn(Invoke<R>) -> bool + Send + Sync + 'static {
+    move |invoke| match check(invoke) {
+        Some(invoke) => handler(invoke),
+        None => true,
+    }
+}
+
+/// Returns the invoke back if the call may proceed, and rejects it otherwise.
+fn check<R: Runtime>(invoke: Invoke<R>) -> Option<Invoke<R>> {
+    let command = invoke.message.command();
+    let webview = invoke.message.webview_ref();
+    if is_allowed(webview, command) {
+        return Some(invoke);
+    }
+
+    tracing::warn!(command, window = webview.label(), "rejected Authium command from a window that is not allowed");
+    let error = format!("{} is not allowed to call {}", webview.label(), command);
+    invoke.resolver.reject(error);
+    None
+}
+
+fn is_allowed<R: Runtime>(webview: &Webview<R>, command: &str) -> bool {
+    if webview.label().starts_with(SIGN_IN_WINDOW) {
+        return false;
+    }
+
+    let config = webview.state::<AuthiumConfig>();
+    let Some(allowed) = config.allowed_windows.get(command).or_else(|| config.allowed_windows.get("*")) else {
+        return true;
+    };
+
+    let caller = webview.url().ok().map(|url| origin(&url));
+    allowed
+        .iter()
+        .any(|entry| entry == webview.label() || caller.as_deref() == Some(entry.trim_end_matches('/')))
+}
+
+/// `scheme://host[:port]` of `url`. Unlike [`Url::origin`] this also works for the custom
+/// schemes apps are served from, such as `tauri://localhost`.
+fn origin(url: &Url) -> String {
+    let mut origin = format!("{}://{}", url.scheme(), url.host_str().unwrap_or_default());
+    if let Some(port) = url.port() {
+        origin.push_str(&format!(":{}", port));
+    }
+    origin
+}
@@ -0,0 +1,72 @@
+use std::time::Duration;
+
+use tauri::Runtime;
+use tokio::sync::watch;
+
+use crate::{server::AuthorizeParams, AuthState, Authium, Error, User};
+
+/// Allowance for clock differences between Authium and the device when checking `auth_time`.
+const CLOCK_SKEW: Duration = Duration::from_secs(60);
+
+/// The parts of the API that work the same on every platform. `sign_in` and `authorize_again`
+/// live in `desktop.rs` and `mobile.rs`.
+impl<R: Runtime> Authium<R> {
+  /// Ends the session, on every device of the user if `everywhere` is set.
+  pub async fn logout(&self, everywhere: bool) {
+    crate::user::logout(everywhere).await
+  }
+
+  /// The signed in user, if any.
+  pub fn user(&self) -> Option<User> {
+    crate::user::get_user()
+  }
+
+  /// Refreshes the access token if it expired and, with `refresh_data`, reloads the user.
+  pub async fn refresh(&self, refresh_data: bool) -> crate::Result<()> {
+    crate::user::refresh_user(refresh_data).await.map_err(Error::Refresh)
+  }
+
+  /// A valid access token for calling other APIs on behalf of the user, refreshed first if
+  /// it expired.
+  pub async fn access_token(&self) -> crate::Result<String> {
+    if crate::user::access_token().is_none() {
+      return Err(Error::NotSignedIn);
+    }
+    self.refresh(false).await?;
+    crate::user::access_token().ok_or(Error::NotSignedIn)
+  }
+
+  /// Time since the user last signed in, as opposed to the session being refreshed. `None`
+  /// without a session.
+  pub fn auth_age(&self) -> Option<Duration> {
+    crate::user::auth_age()
+  }
+
+  /// Makes sure the user signed in within `max_age`, asking them to sign in again
+  /// (`prompt=login`) if they did not. Use it before sensitive actions. Fails if the user
+  /// cancels or someone else signs in, in which case the current session is kept.
+  pub async fn reauthenticate(&self, max_age: Duration) -> crate::Result<()> {
+    let user = self.user().ok_or(Error::NotSignedIn)?;
+    if self.auth_age().is_some_and(|age| age <= max_age) {
+      return Ok(());
+    }
+
+    let params = AuthorizeParams { max_age: Some(max_age.as_secs()), ..Default::default() };
+    self.authorize_again(params, user.id).await?;
+    match self.auth_age() {
+      Some(age) if age <= max_age + CLOCK_SKEW => Ok(()),
+      _ => Err(Error::SignIn("Authium did not ask the user to sign in again".into())),
+    }
+  }
+
+  pub fn auth_state(&self) -> AuthState {
+    crate::user::auth_state()
+  }
+
+  /// Subscribes to changes of [`Authium::auth_state`]. The receiver starts out with the
+  /// current state, so background tasks can wait for a sign-in with
+  /// `receiver.wait_for(|state| matches!(state, AuthState::SignedIn(_)))`.
+  pub fn subscribe(&self) -> watch::Receiver<AuthState> {
+    crate::user::subscribe()
+  }
+}
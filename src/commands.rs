@@ -1,25 +1,22 @@
-use tauri::{Manager, State, Url, WebviewUrl, WebviewWindowBuilder};
+use std::time::Duration;
+
+use tauri::{ipc::{InvokeBody, Request}, Emitter, State};
 use tauri::{AppHandle, command, Runtime};
 
-use crate::{models::*, AuthiumConfig};
+use crate::{models::*, AuthiumConfig, AuthiumExt};
 
 #[command]
-pub(crate) fn sign_in<R: Runtime>(app: AppHandle<R>, config: State<AuthiumConfig>, expiry: Option<u64>) {
-    let port = config.port.unwrap_or(6483);
-    let mut url_str = format!("http://localhost:{}/login", port);
-    if let Some(expiry) = expiry {
-        url_str.push_str(&format!("?expiry={}", expiry));
-    }
-    let url = Url::parse(&url_str).expect("invalid url");
-
-    tauri::async_runtime::spawn(async move {
-        let window = WebviewWindowBuilder::new(&app, "authium-auth-cb-signin".to_string(), WebviewUrl::External(url))
-            .title("Authium | Sign In")
-            .build()
-            .expect("Failed to create window");
+pub(crate) async fn sign_in<R: Runtime>(app: AppHandle<R>, payload: SignInPayload) -> Result<(), String> {
+    app.authium().sign_in_with_acr(payload.expiry, payload.acr).await.map_err(|e| e.to_string())
+}
 
-        window.show().expect("Failed to show window");
-    });
+#[command]
+pub(crate) async fn sign_in_device<R: Runtime>(app: AppHandle<R>, config: State<'_, AuthiumConfig>, payload: SignInPayload) -> Result<DeviceCode, String> {
+    if payload.acr.is_some() {
+        return Err("The device flow does not support requesting an authentication context".into());
+    }
+    let config = config.inner().clone();
+    crate::device::start(app, &config, payload.expiry).await
 }
 
 #[command]
@@ -28,8 +25,8 @@ pub(crate) fn get_user() -> Option<User> {
 }
 
 #[command]
-pub(crate) fn logout() {
-    crate::user::logout();
+pub(crate) async fn logout(payload: LogoutPayload) {
+    crate::user::logout(payload.everywhere).await;
 }
 
 #[command]
@@ -38,6 +35,73 @@ pub(crate) fn is_logged_in() -> bool {
 }
 
 #[command]
-pub(crate) fn refresh(refresh_data: bool) {
-    let _ = crate::user::refresh_user(refresh_data);
-}
\ No newline at end of file
+pub(crate) fn get_auth_state<R: Runtime>(app: AppHandle<R>) -> AuthState {
+    app.authium().auth_state()
+}
+
+#[command]
+pub(crate) async fn get_access_token<R: Runtime>(app: AppHandle<R>) -> Result<String, String> {
+    app.authium().access_token().await.map_err(|e| e.to_string())
+}
+
+#[command]
+pub(crate) async fn refresh<R: Runtime>(app: AppHandle<R>, payload: RefreshPayload) -> Result<(), String> {
+    app.authium().refresh(payload.refresh_data).await.map_err(|e| e.to_string())
+}
+
+#[command]
+pub(crate) async fn reauthenticate<R: Runtime>(app: AppHandle<R>, payload: ReauthenticatePayload) -> Result<(), String> {
+    app.authium()
+        .reauthenticate(Duration::from_secs(payload.max_age))
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[command]
+pub(crate) async fn update_user<R: Runtime>(app: AppHandle<R>, payload: UserUpdate) -> Result<User, String> {
+    let user = crate::user::update_user(payload).await?;
+    let _ = app.emit("authium:user-updated", &user);
+    Ok(user)
+}
+
+/// Carries the MIME type of the avatar, whose bytes are sent as the raw request body.
+const AVATAR_MIME_TYPE_HEADER: &str = "Authium-Mime-Type";
+
+#[command]
+pub(crate) async fn update_avatar<R: Runtime>(app: AppHandle<R>, request: Request<'_>) -> Result<User, String> {
+    let InvokeBody::Raw(data) = request.body() else {
+        return Err("Avatar must be sent as raw bytes".into());
+    };
+    let mime_type = request
+        .headers()
+        .get(AVATAR_MIME_TYPE_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .ok_or_else(|| format!("Missing {} header", AVATAR_MIME_TYPE_HEADER))?;
+    let user = crate::user::update_avatar(data.clone(), mime_type.to_string()).await?;
+    let _ = app.emit("authium:user-updated", &user);
+    Ok(user)
+}
+
+#[command]
+pub(crate) async fn get_session_info(config: State<'_, AuthiumConfig>, payload: SessionInfoPayload) -> Result<SessionInfo, String> {
+    if !payload.introspect {
+        return Ok(crate::user::get_session_info());
+    }
+
+    if config.app_id.is_none() || config.api_key.is_none() {
+        return Err("API key or App ID not set in configuration".into());
+    }
+    crate::user::introspect_session().await
+}
+
+#[command]
+pub(crate) async fn start_totp_enrollment() -> Result<TotpEnrollment, String> {
+    crate::user::start_totp_enrollment().await
+}
+
+#[command]
+pub(crate) async fn confirm_totp_enrollment<R: Runtime>(app: AppHandle<R>, payload: ConfirmTotpPayload) -> Result<User, String> {
+    let user = crate::user::confirm_totp_enrollment(&payload.enrollment_id, &payload.code).await?;
+    let _ = app.emit("authium:user-updated", &user);
+    Ok(user)
+}
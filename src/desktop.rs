@@ -1,7 +1,21 @@
+use std::sync::{
+  atomic::{AtomicU32, Ordering},
+  Arc, Mutex,
+};
+
 use serde::de::DeserializeOwned;
-use tauri::{plugin::PluginApi, AppHandle, Runtime};
+use tauri::{
+  plugin::PluginApi, AppHandle, Emitter, Listener, Manager, Runtime, Url, WebviewUrl, WebviewWindow,
+  WebviewWindowBuilder, WindowEvent,
+};
+use tokio::sync::oneshot;
+
+use crate::{access::SIGN_IN_WINDOW, server::AuthorizeParams, AuthiumConfig, Error};
 
-use crate::models::*;
+/// Label of the open sign-in window. Every window gets a fresh label, as the label of a closed
+/// window stays taken until the window is fully destroyed.
+static OPEN_WINDOW: Mutex<Option<String>> = Mutex::new(None);
+static WINDOWS_OPENED: AtomicU32 = AtomicU32::new(0);
 
 pub fn init<R: Runtime, C: DeserializeOwned>(
   app: &AppHandle<R>,
@@ -14,9 +28,152 @@ pub fn init<R: Runtime, C: DeserializeOwned>(
 pub struct Authium<R: Runtime>(AppHandle<R>);
 
 impl<R: Runtime> Authium<R> {
-  pub fn ping(&self, payload: PingRequest) -> crate::Result<PingResponse> {
-    Ok(PingResponse {
-      value: payload.value,
-    })
+  /// Opens the Authium sign-in page in a window, or focuses the one already open. The session
+  /// starts once Authium redirects back to the local callback server, which emits
+  /// `authium:login-success` and closes the window. Closing the window before that emits
+  /// `authium:login-cancelled`.
+  pub async fn sign_in(&self, expiry: Option<u64>) -> crate::Result<()> {
+    self.sign_in_with_acr(expiry, None).await
+  }
+
+  /// Like [`Authium::sign_in`], requesting the authentication context class `acr`, e.g. `mfa`.
+  pub async fn sign_in_with_acr(&self, expiry: Option<u64>, acr: Option<String>) -> crate::Result<()> {
+    if let Some(window) = sign_in_window(&self.0) {
+      window.unminimize()?;
+      window.set_focus()?;
+      return Ok(());
+    }
+    self.open_sign_in_window(&AuthorizeParams { expiry, acr, ..Default::default() }, None)
+  }
+
+  /// Runs the sign-in again for `user_id` and waits for it to finish, see
+  /// [`Authium::reauthenticate`].
+  pub(crate) async fn authorize_again(&self, params: AuthorizeParams, user_id: i64) -> crate::Result<()> {
+    if sign_in_window(&self.0).is_some() {
+      return Err(Error::SignIn("a sign-in is already in progress".into()));
+    }
+
+    let (tx, rx) = oneshot::channel();
+    let tx = Arc::new(Mutex::new(Some(tx)));
+    let listen = |event: &str, outcome: fn(&str) -> Result<(), String>| {
+      let tx = tx.clone();
+      self.0.listen(event, move |event| {
+        if let Some(tx) = tx.lock().unwrap().take() {
+          let _ = tx.send(outcome(event.payload()));
+        }
+      })
+    };
+    let listeners = [
+      listen("authium:login-success", |_| Ok(())),
+      listen("authium:login-failed", |payload| {
+        Err(serde_json::from_str(payload).unwrap_or_else(|_| payload.to_string()))
+      }),
+      listen("authium:login-cancelled", |_| Err("sign-in was cancelled".into())),
+    ];
+
+    let result = match self.open_sign_in_window(&params, Some(user_id)) {
+      Ok(()) => rx
+        .await
+        .unwrap_or_else(|_| Err("sign-in was abandoned".into()))
+        .map_err(Error::SignIn),
+      Err(e) => Err(e),
+    };
+    for id in listeners {
+      self.0.unlisten(id);
+    }
+    result
+  }
+
+  fn open_sign_in_window(&self, params: &AuthorizeParams, expected_user: Option<i64>) -> crate::Result<()> {
+    let config = self.0.state::<AuthiumConfig>();
+    let options = config.sign_in_window.clone().unwrap_or_default();
+    let mut url = Url::parse(&format!("http://localhost:{}/login", config.port.unwrap_or(6483)))
+      .map_err(|e| Error::SignIn(e.to_string()))?;
+    if let Some(expiry) = params.expiry {
+      url.query_pairs_mut().append_pair("expiry", &expiry.to_string());
+    }
+    if let Some(max_age) = params.max_age {
+      url.query_pairs_mut().append_pair("maxAge", &max_age.to_string());
+    }
+    if let Some(acr) = &params.acr {
+      url.query_pairs_mut().append_pair("acr", acr);
+    }
+
+    let parent = match &options.parent {
+      Some(label) => Some(
+        self
+          .0
+          .get_webview_window(label)
+          .ok_or_else(|| Error::SignIn(format!("parent window `{}` not found", label)))?,
+      ),
+      None => None,
+    };
+
+    let label = format!("{}-{}", SIGN_IN_WINDOW, WINDOWS_OPENED.fetch_add(1, Ordering::Relaxed));
+    let mut builder = WebviewWindowBuilder::new(&self.0, &label, WebviewUrl::External(url))
+      .title(options.title.as_deref().unwrap_or("Authium | Sign In"));
+    if options.width.is_some() || options.height.is_some() {
+      builder = builder.inner_size(options.width.unwrap_or(800.0), options.height.unwrap_or(600.0));
+    }
+    if let Some(parent) = &parent {
+      builder = builder.parent(parent)?;
+    }
+    let window = builder.build()?;
+    *OPEN_WINDOW.lock().unwrap() = Some(label.clone());
+    crate::server::begin_sign_in(expected_user);
+
+    let modal_parent = parent.filter(|_| options.modal);
+    if let Some(parent) = &modal_parent {
+      parent.set_enabled(false)?;
+    }
+    let app = self.0.clone();
+    window.on_window_event(move |event| {
+      if let WindowEvent::Destroyed = event {
+        if let Some(parent) = &modal_parent {
+          let _ = parent.set_enabled(true);
+          let _ = parent.set_focus();
+        }
+        sign_in_window_destroyed(&app, &label);
+      }
+    });
+    window.show()?;
+    Ok(())
+  }
+}
+
+fn sign_in_window<R: Runtime>(app: &AppHandle<R>) -> Option<WebviewWindow<R>> {
+  let label = OPEN_WINDOW.lock().unwrap().clone()?;
+  app.get_webview_window(&label)
+}
+
+/// Closes the sign-in window once its callback has been handled.
+pub(crate) fn close_sign_in_window<R: Runtime>(app: &AppHandle<R>) {
+  if let Some(window) = sign_in_window(app) {
+    OPEN_WINDOW.lock().unwrap().take();
+    let _ = window.close();
+  }
+}
+
+/// Cancels the sign-in of the window `label` if it was destroyed before its callback arrived.
+fn sign_in_window_destroyed<R: Runtime>(app: &AppHandle<R>, label: &str) {
+  {
+    let mut open = OPEN_WINDOW.lock().unwrap();
+    if open.as_deref() != Some(label) {
+      return;
+    }
+    open.take();
+  }
+  if crate::server::cancel_sign_in() {
+    let _ = app.emit("authium:login-cancelled", ());
+  }
+}
+
+/// Destroys the sign-in window and handles it right away, see
+/// [`crate::testing::destroy_sign_in_window`].
+#[cfg(feature = "testing")]
+pub(crate) fn destroy_sign_in_window<R: Runtime>(app: &AppHandle<R>) {
+  if let Some(window) = sign_in_window(app) {
+    let _ = window.destroy();
+    sign_in_window_destroyed(app, window.label());
   }
 }
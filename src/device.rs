@@ -0,0 +1,165 @@
+use std::{sync::atomic::{AtomicU64, Ordering}, time::{Duration, SystemTime}};
+
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Runtime};
+
+use crate::user::{sign_in, IssuedTokens};
+use crate::{daemon_endpoint, http::RequestBuilderExt as _, redact::Redacted, AuthiumConfig, DeviceCode, User};
+
+const DEVICE_CODE_GRANT: &str = "urn:ietf:params:oauth:grant-type:device_code";
+const DEFAULT_INTERVAL: u64 = 5;
+const SLOW_DOWN_INCREMENT: u64 = 5;
+
+/// Incremented for every device flow that is started, so pollers of older flows stop.
+static FLOW_ID: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Serialize)]
+struct DeviceCodeRequest<'a> {
+    client_id: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    exp: Option<u64>,
+}
+
+#[derive(Deserialize)]
+struct DeviceCodeData {
+    device_code: String,
+    user_code: String,
+    verification_uri: String,
+    verification_uri_complete: Option<String>,
+    expires_in: u64,
+    interval: Option<u64>,
+}
+
+#[derive(Serialize)]
+struct TokenRequest<'a> {
+    grant_type: &'a str,
+    device_code: &'a str,
+    client_id: &'a str,
+}
+
+#[derive(Deserialize)]
+struct TokenData {
+    access_token: String,
+    refresh_token: String,
+    expires_in: u64,
+    scope: Option<String>,
+    token_type: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct TokenError {
+    error: String,
+    error_description: Option<String>,
+}
+
+/// Starts the OAuth device authorization grant.
+///
+/// The user code is emitted as `authium:device-code` and returned. Authium is then polled in the
+/// background until the user approves the request, which ends in `authium:login-success`, or
+/// the flow fails, which ends in `authium:device-error`.
+#[tracing::instrument(name = "authium.device_sign_in", skip_all, fields(expiry = ?expiry), err)]
+pub async fn start<R: Runtime>(handle: AppHandle<R>, config: &AuthiumConfig, expiry: Option<u64>) -> Result<DeviceCode, String> {
+    let Some(app_id) = config.app_id.clone() else {
+        return Err("App ID not set in configuration".into());
+    };
+
+    let client = crate::http::client();
+    let response = client.post(format!("{}/device/code", daemon_endpoint()))
+        .form(&DeviceCodeRequest { client_id: &app_id, exp: expiry })
+        .send_with_retry()
+        .await
+        .map_err(|e| format!("Failed to request device code: {}", e))?;
+
+    let status = response.status();
+    if !status.is_success() {
+        let body: serde_json::Value = response.json().await.unwrap_or_default();
+        return Err(format!("Failed to request device code: {} - {}", status, body));
+    }
+
+    let data = response.json::<DeviceCodeData>().await
+        .map_err(|e| format!("Failed to parse device code: {}", e))?;
+    let code = DeviceCode {
+        user_code: data.user_code.clone(),
+        verification_uri: data.verification_uri.clone(),
+        verification_uri_complete: data.verification_uri_complete.clone(),
+        expires_in: data.expires_in,
+        interval: data.interval.unwrap_or(DEFAULT_INTERVAL),
+    };
+    let _ = handle.emit("authium:device-code", &code);
+
+    let flow_id = FLOW_ID.fetch_add(1, Ordering::SeqCst) + 1;
+    tauri::async_runtime::spawn(async move {
+        match poll(&client, &app_id, &data, flow_id).await {
+            Ok(Some(u)) => {
+                let _ = handle.emit("authium:login-success", u);
+            }
+            Ok(None) => {}
+            Err(e) => {
+                tracing::warn!(error = %e, "device sign-in failed");
+                let _ = handle.emit("authium:device-error", e);
+            }
+        }
+    });
+
+    Ok(code)
+}
+
+/// Polls the token endpoint until the flow completes. Returns `None` if a newer flow replaced this one.
+#[tracing::instrument(name = "authium.device_poll", skip_all, fields(flow_id = flow_id, device_code = %Redacted(&data.device_code)))]
+async fn poll(client: &Client, app_id: &str, data: &DeviceCodeData, flow_id: u64) -> Result<Option<User>, String> {
+    let deadline = SystemTime::now() + Duration::from_secs(data.expires_in);
+    let mut interval = data.interval.unwrap_or(DEFAULT_INTERVAL);
+
+    loop {
+        tokio::time::sleep(Duration::from_secs(interval)).await;
+        if FLOW_ID.load(Ordering::SeqCst) != flow_id {
+            tracing::debug!("superseded by a newer device flow");
+            return Ok(None);
+        }
+        if SystemTime::now() >= deadline {
+            return Err("The device code expired before sign-in was completed".into());
+        }
+
+        let request = TokenRequest {
+            grant_type: DEVICE_CODE_GRANT,
+            device_code: &data.device_code,
+            client_id: app_id,
+        };
+        let Ok(response) = client.post(format!("{}/token", daemon_endpoint())).form(&request).send_with_retry().await else {
+            // the network may come and go while the user signs in on another device
+            tracing::debug!("token endpoint unreachable, polling again");
+            continue;
+        };
+
+        if response.status().is_success() {
+            let token = response.json::<TokenData>().await
+                .map_err(|e| format!("Failed to parse token response: {}", e))?;
+            let tokens = IssuedTokens {
+                access_token: token.access_token,
+                refresh_token: token.refresh_token,
+                expiry: SystemTime::now() + Duration::from_secs(token.expires_in),
+                scope: token.scope,
+                token_type: token.token_type,
+                auth_time: None,
+            };
+            return sign_in(tokens, None).await.map(Some);
+        }
+
+        let status = response.status();
+        if status.is_server_error() {
+            tracing::debug!(%status, "token endpoint failed, polling again");
+            continue;
+        }
+        let error = response.json::<TokenError>().await
+            .map_err(|_| format!("Failed to poll device token: {}", status))?;
+        match error.error.as_str() {
+            "authorization_pending" => {}
+            "slow_down" => {
+                interval += SLOW_DOWN_INCREMENT;
+                tracing::debug!(interval, "slowing down device polling");
+            }
+            _ => return Err(error.error_description.unwrap_or(error.error)),
+        }
+    }
+}
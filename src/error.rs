@@ -6,6 +6,28 @@ pub type Result<T> = std::result::Result<T, Error>;
 pub enum Error {
   #[error(transparent)]
   Io(#[from] std::io::Error),
+  #[error("failed to encrypt session data")]
+  Encryption,
+  #[error("failed to serialize session data: {0}")]
+  Serialization(#[from] serde_json::Error),
+  #[error("failed to configure the HTTP client: {0}")]
+  Http(#[from] reqwest::Error),
+  #[error("stored session is corrupted ({reason}) and was moved to {}", path.display())]
+  CorruptedStorage { path: std::path::PathBuf, reason: String },
+  #[error("missing Authium configuration: set `{0}` in code or under `plugins.authium` in tauri.conf.json")]
+  MissingConfig(&'static str),
+  #[error("invalid value `{value}` for {name}")]
+  InvalidConfig { name: &'static str, value: String },
+  #[error("unknown Authium profile `{0}`: add it under `plugins.authium.profiles` in tauri.conf.json")]
+  UnknownProfile(String),
+  #[error("sign-in failed: {0}")]
+  SignIn(String),
+  #[error("not signed in")]
+  NotSignedIn,
+  #[error("failed to refresh the session: {0}")]
+  Refresh(String),
+  #[error(transparent)]
+  Tauri(#[from] tauri::Error),
   #[cfg(mobile)]
   #[error(transparent)]
   PluginInvoke(#[from] tauri::plugin::mobile::PluginInvokeError),
@@ -0,0 +1,150 @@
+use std::{
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Mutex,
+    },
+    time::Duration,
+};
+
+use rand::Rng as _;
+use reqwest::{header::RETRY_AFTER, Certificate, Client, Method, Proxy, RequestBuilder, Response, StatusCode};
+use tauri::PackageInfo;
+
+use crate::{AuthiumConfig, Result};
+
+const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+const DEFAULT_READ_TIMEOUT: Duration = Duration::from_secs(30);
+const DEFAULT_MAX_RETRIES: u32 = 3;
+/// Delay before the first retry, doubled for every further one.
+const BASE_RETRY_DELAY: Duration = Duration::from_millis(250);
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(30);
+
+/// The client used for every call to Authium, configured once during setup.
+static CLIENT: Mutex<Option<Client>> = Mutex::new(None);
+static MAX_RETRIES: AtomicU32 = AtomicU32::new(DEFAULT_MAX_RETRIES);
+/// App id and API key the app authenticates itself with on back-channel requests.
+static CLIENT_CREDENTIALS: Mutex<Option<(String, String)>> = Mutex::new(None);
+
+/// Builds the shared client from the plugin configuration.
+pub(crate) fn configure(config: &AuthiumConfig, package: &PackageInfo) -> Result<()> {
+    let user_agent = config.user_agent.clone().unwrap_or_else(|| {
+        format!(
+            "{}/{} tauri-plugin-authium/{}",
+            package.name,
+            package.version,
+            env!("CARGO_PKG_VERSION")
+        )
+    });
+
+    let mut builder = Client::builder()
+        .user_agent(user_agent)
+        .connect_timeout(config.connect_timeout.map(Duration::from_secs).unwrap_or(DEFAULT_CONNECT_TIMEOUT))
+        .read_timeout(config.read_timeout.map(Duration::from_secs).unwrap_or(DEFAULT_READ_TIMEOUT));
+
+    if let Some(proxy) = &config.proxy {
+        builder = builder.proxy(Proxy::all(proxy)?);
+    }
+    if let Some(path) = &config.ca_certificates {
+        for certificate in Certificate::from_pem_bundle(&std::fs::read(path)?)? {
+            builder = builder.add_root_certificate(certificate);
+        }
+    }
+
+    *CLIENT.lock().unwrap() = Some(builder.build()?);
+    MAX_RETRIES.store(config.max_retries.unwrap_or(DEFAULT_MAX_RETRIES), Ordering::Relaxed);
+    *CLIENT_CREDENTIALS.lock().unwrap() = config.app_id.clone().zip(config.api_key.clone());
+    Ok(())
+}
+
+/// Returns the shared client. Before setup a client with the default settings is used.
+pub(crate) fn client() -> Client {
+    CLIENT
+        .lock()
+        .unwrap()
+        .get_or_insert_with(|| {
+            Client::builder()
+                .connect_timeout(DEFAULT_CONNECT_TIMEOUT)
+                .read_timeout(DEFAULT_READ_TIMEOUT)
+                .build()
+                .unwrap_or_default()
+        })
+        .clone()
+}
+
+pub(crate) trait RequestBuilderExt {
+    /// Authenticates the app with HTTP Basic auth using its app id and API key, if both are
+    /// configured.
+    fn client_auth(self) -> Self;
+
+    /// Sends the request, retrying failures that are known to be transient: connections that
+    /// could not be established, 429 and 503 responses, and for idempotent requests also
+    /// timeouts and 502 and 504 responses, after which a request may already have taken effect.
+    /// Retries back off exponentially with jitter unless Authium sends `Retry-After`. Requests
+    /// with streaming bodies cannot be replayed and are sent once.
+    async fn send_with_retry(self) -> reqwest::Result<Response>;
+}
+
+impl RequestBuilderExt for RequestBuilder {
+    fn client_auth(self) -> Self {
+        match CLIENT_CREDENTIALS.lock().unwrap().clone() {
+            Some((app_id, api_key)) => self.basic_auth(app_id, Some(api_key)),
+            None => self,
+        }
+    }
+
+    async fn send_with_retry(self) -> reqwest::Result<Response> {
+        let (client, request) = self.build_split();
+        let request = request?;
+        let idempotent = matches!(*request.method(), Method::GET | Method::HEAD | Method::PUT | Method::DELETE);
+        let max_retries = MAX_RETRIES.load(Ordering::Relaxed);
+
+        let mut attempt = 0;
+        loop {
+            let retry = if attempt < max_retries { request.try_clone() } else { None };
+            let Some(retry) = retry else {
+                return client.execute(request).await;
+            };
+
+            let delay = match client.execute(retry).await {
+                Ok(response) if is_transient(response.status(), idempotent) => {
+                    tracing::debug!(status = %response.status(), attempt, "Authium is unavailable");
+                    retry_after(&response).unwrap_or_else(|| backoff(attempt))
+                }
+                Err(e) if e.is_connect() || (idempotent && e.is_timeout()) => {
+                    tracing::debug!(error = %e, attempt, "request to Authium failed");
+                    backoff(attempt)
+                }
+                result => return result,
+            };
+            attempt += 1;
+            tokio::time::sleep(delay).await;
+        }
+    }
+}
+
+/// Whether a response is worth retrying. 429 and 503 mean Authium did not process the request,
+/// while a gateway may answer 502 or 504 after it did, so those are retried for idempotent
+/// requests only.
+fn is_transient(status: StatusCode, idempotent: bool) -> bool {
+    match status {
+        StatusCode::TOO_MANY_REQUESTS | StatusCode::SERVICE_UNAVAILABLE => true,
+        StatusCode::BAD_GATEWAY | StatusCode::GATEWAY_TIMEOUT => idempotent,
+        _ => false,
+    }
+}
+
+/// The delay requested by a `Retry-After` header in seconds. The HTTP-date form is not
+/// supported; Authium sends seconds, and a date falls back to the exponential backoff.
+fn retry_after(response: &Response) -> Option<Duration> {
+    let secs = response.headers().get(RETRY_AFTER)?.to_str().ok()?.trim().parse().ok()?;
+    Some(Duration::from_secs(secs).min(MAX_RETRY_DELAY))
+}
+
+/// Exponential backoff with jitter, so that clients that failed together do not retry together.
+fn backoff(attempt: u32) -> Duration {
+    let ceiling = BASE_RETRY_DELAY
+        .saturating_mul(2u32.saturating_pow(attempt))
+        .min(MAX_RETRY_DELAY);
+    let millis = ceiling.as_millis() as u64;
+    Duration::from_millis(rand::rng().random_range(millis / 2..=millis))
+}
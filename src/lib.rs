@@ -1,8 +1,8 @@
-use std::{sync::Mutex, thread};
+use std::{collections::HashMap, sync::Mutex, thread};
 
 use serde::Deserialize;
 use tauri::{
-    plugin::{Builder, TauriPlugin}, Manager, Runtime,
+    plugin::{Builder as PluginBuilder, TauriPlugin}, Manager, Runtime,
 };
 
 pub use models::*;
@@ -12,27 +12,49 @@ mod desktop;
 #[cfg(mobile)]
 mod mobile;
 
+mod access;
+mod api;
 mod commands;
+mod device;
 mod error;
+mod http;
 pub mod models;
+mod redact;
 mod server;
 pub mod user;
 mod storage;
+#[cfg(feature = "testing")]
+pub mod testing;
 
 static DATA_DIR: Mutex<String> = Mutex::new(String::new());
 // Development endpoints
-//static DAEMON_ENDPOINT: &str = "http://localhost:8085/v1";
-//static AUTHIUM_ENDPOINT: &str = "http://localhost:3000";
+//static DEFAULT_DAEMON_ENDPOINT: &str = "http://localhost:8085/v1";
+//static DEFAULT_AUTHIUM_ENDPOINT: &str = "http://localhost:3000";
 // Production endpoints
-static DAEMON_ENDPOINT: &str = "https://api.authium.ezerium.com/v1";
-static AUTHIUM_ENDPOINT: &str = "https://authium.ezerium.com";
+static DEFAULT_DAEMON_ENDPOINT: &str = "https://api.authium.ezerium.com/v1";
+static DEFAULT_AUTHIUM_ENDPOINT: &str = "https://authium.ezerium.com";
+// Overrides from `apiUrl` and `authUrl` in the configuration
+static DAEMON_ENDPOINT: Mutex<String> = Mutex::new(String::new());
+static AUTHIUM_ENDPOINT: Mutex<String> = Mutex::new(String::new());
+
+/// Base URL of the Authium API.
+fn daemon_endpoint() -> String {
+    let endpoint = DAEMON_ENDPOINT.lock().unwrap();
+    if endpoint.is_empty() { DEFAULT_DAEMON_ENDPOINT.to_string() } else { endpoint.clone() }
+}
+
+/// Base URL of the Authium sign-in pages.
+fn authium_endpoint() -> String {
+    let endpoint = AUTHIUM_ENDPOINT.lock().unwrap();
+    if endpoint.is_empty() { DEFAULT_AUTHIUM_ENDPOINT.to_string() } else { endpoint.clone() }
+}
 
 pub use error::{Error, Result};
 
 #[cfg(desktop)]
-use desktop::Authium;
+pub use desktop::Authium;
 #[cfg(mobile)]
-use mobile::Authium;
+pub use mobile::Authium;
 
 use crate::storage::setup_storage;
 
@@ -47,61 +69,362 @@ impl<R: Runtime, T: Manager<R>> crate::AuthiumExt<R> for T {
     }
 }
 
-#[derive(Clone, Deserialize)]
+/// Plugin configuration, read from `plugins.authium` in `tauri.conf.json` and overridden by
+/// the values given to [`Builder`] and the `AUTHIUM_*` environment variables.
+#[derive(Clone, Default, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct AuthiumConfig {
     pub api_key: Option<String>,
     pub app_id: Option<String>,
     pub port: Option<u16>,
+    /// Overrides the Authium API base URL, e.g. `https://api.authium.ezerium.com/v1`.
+    pub api_url: Option<String>,
+    /// Overrides the base URL of the Authium sign-in pages, e.g. `https://authium.ezerium.com`.
+    pub auth_url: Option<String>,
+    /// Seconds to wait for a connection to Authium. Defaults to 10.
+    pub connect_timeout: Option<u64>,
+    /// Seconds to wait for Authium to send data once connected. Defaults to 30.
+    pub read_timeout: Option<u64>,
+    /// Proxy used for all Authium calls, e.g. `http://proxy.internal:3128`.
+    pub proxy: Option<String>,
+    /// PEM bundle of additional root certificates to trust, for proxies that intercept TLS.
+    pub ca_certificates: Option<std::path::PathBuf>,
+    /// Overrides the `User-Agent` header, which defaults to the app name and version.
+    pub user_agent: Option<String>,
+    /// How often a call to Authium is retried after a transient failure. Defaults to 3.
+    pub max_retries: Option<u32>,
+    /// URL scheme the app registers to receive the sign-in redirect on Android and iOS,
+    /// e.g. `com.example.app`. Authium redirects to `<scheme>://authium/callback`.
+    pub callback_scheme: Option<String>,
+    /// Directory for the session files. Defaults to the app data directory.
+    pub store: Option<std::path::PathBuf>,
+    /// Authentication context class requested at every sign-in, e.g. `mfa` to require a
+    /// second factor.
+    pub acr_values: Option<String>,
+    /// Size and placement of the desktop sign-in window.
+    pub sign_in_window: Option<SignInWindowConfig>,
+    /// Windows that may call each command, keyed by command name, with `"*"` applying to
+    /// commands without their own entry. Windows are given by label or by origin, e.g.
+    /// `https://app.example.com` or `tauri://localhost`. Commands without an entry may be called
+    /// from every window the app's capabilities allow, except the sign-in window.
+    #[serde(default)]
+    pub allowed_windows: HashMap<String, Vec<String>>,
+    /// Name of the entry in `profiles` to apply, e.g. `staging`.
+    pub profile: Option<String>,
+    /// Named sets of values applied over the rest of the configuration when selected, so that
+    /// one build can target several Authium environments.
+    #[serde(default)]
+    pub profiles: HashMap<String, AuthiumConfig>,
+}
+
+/// How the desktop sign-in window is shown. Values left unset use the window defaults.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SignInWindowConfig {
+    pub title: Option<String>,
+    pub width: Option<f64>,
+    pub height: Option<f64>,
+    /// Label of the window the sign-in window belongs to, e.g. `main`.
+    pub parent: Option<String>,
+    /// Disables the parent window while the sign-in window is open.
+    #[serde(default)]
+    pub modal: bool,
+}
+
+/// Environment variables read at startup, each overriding the value of the same name.
+const ENV_OVERRIDES: [&str; 9] = [
+    "AUTHIUM_PROFILE",
+    "AUTHIUM_API_KEY",
+    "AUTHIUM_APP_ID",
+    "AUTHIUM_API_URL",
+    "AUTHIUM_AUTH_URL",
+    "AUTHIUM_PORT",
+    "AUTHIUM_PROXY",
+    "AUTHIUM_CALLBACK_SCHEME",
+    "AUTHIUM_STORE",
+];
+
+impl std::fmt::Debug for AuthiumConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AuthiumConfig")
+            .field("api_key", &self.api_key.as_deref().map(redact::Redacted))
+            .field("app_id", &self.app_id)
+            .field("port", &self.port)
+            .field("api_url", &self.api_url)
+            .field("auth_url", &self.auth_url)
+            .field("connect_timeout", &self.connect_timeout)
+            .field("read_timeout", &self.read_timeout)
+            .field("proxy", &self.proxy)
+            .field("ca_certificates", &self.ca_certificates)
+            .field("user_agent", &self.user_agent)
+            .field("max_retries", &self.max_retries)
+            .field("callback_scheme", &self.callback_scheme)
+            .field("store", &self.store)
+            .field("acr_values", &self.acr_values)
+            .field("sign_in_window", &self.sign_in_window)
+            .field("allowed_windows", &self.allowed_windows)
+            .field("profile", &self.profile)
+            .field("profiles", &self.profiles)
+            .finish()
+    }
 }
 
 impl AuthiumConfig {
     pub fn new(api_key: String, app_id: String) -> Self {
-        Self { api_key: Some(api_key), app_id: Some(app_id), port: None }
+        Self { api_key: Some(api_key), app_id: Some(app_id), ..Default::default() }
     }
 
     pub fn with_port(mut self, port: u16) -> Self {
         self.port = Some(port);
         self
     }
+
+    pub fn with_endpoints(mut self, api_url: String, auth_url: String) -> Self {
+        self.api_url = Some(api_url);
+        self.auth_url = Some(auth_url);
+        self
+    }
+
+    /// Returns this configuration with every value set in `overrides` replaced.
+    pub fn merge(self, overrides: AuthiumConfig) -> Self {
+        Self {
+            api_key: overrides.api_key.or(self.api_key),
+            app_id: overrides.app_id.or(self.app_id),
+            port: overrides.port.or(self.port),
+            api_url: overrides.api_url.or(self.api_url),
+            auth_url: overrides.auth_url.or(self.auth_url),
+            connect_timeout: overrides.connect_timeout.or(self.connect_timeout),
+            read_timeout: overrides.read_timeout.or(self.read_timeout),
+            proxy: overrides.proxy.or(self.proxy),
+            ca_certificates: overrides.ca_certificates.or(self.ca_certificates),
+            user_agent: overrides.user_agent.or(self.user_agent),
+            max_retries: overrides.max_retries.or(self.max_retries),
+            callback_scheme: overrides.callback_scheme.or(self.callback_scheme),
+            store: overrides.store.or(self.store),
+            acr_values: overrides.acr_values.or(self.acr_values),
+            sign_in_window: overrides.sign_in_window.or(self.sign_in_window),
+            allowed_windows: {
+                let mut allowed_windows = self.allowed_windows;
+                allowed_windows.extend(overrides.allowed_windows);
+                allowed_windows
+            },
+            profile: overrides.profile.or(self.profile),
+            profiles: {
+                let mut profiles = self.profiles;
+                profiles.extend(overrides.profiles);
+                profiles
+            },
+        }
+    }
+
+    /// Reads the `AUTHIUM_*` environment variables. Unset and empty variables are ignored.
+    pub fn from_env() -> Result<Self> {
+        Self::from_vars(|name| std::env::var(name).ok())
+    }
+
+    /// Like [`AuthiumConfig::from_env`], reading the variables through `var`.
+    pub fn from_vars(var: impl Fn(&str) -> Option<String>) -> Result<Self> {
+        let mut config = Self::default();
+        for name in ENV_OVERRIDES {
+            let Some(value) = var(name).filter(|v| !v.trim().is_empty()) else {
+                continue;
+            };
+            match name {
+                "AUTHIUM_PROFILE" => config.profile = Some(value),
+                "AUTHIUM_API_KEY" => config.api_key = Some(value),
+                "AUTHIUM_APP_ID" => config.app_id = Some(value),
+                "AUTHIUM_API_URL" => config.api_url = Some(value),
+                "AUTHIUM_AUTH_URL" => config.auth_url = Some(value),
+                "AUTHIUM_PORT" => {
+                    let port = value.trim().parse().map_err(|_| Error::InvalidConfig { name, value })?;
+                    config.port = Some(port);
+                }
+                "AUTHIUM_PROXY" => config.proxy = Some(value),
+                "AUTHIUM_CALLBACK_SCHEME" => config.callback_scheme = Some(value),
+                "AUTHIUM_STORE" => config.store = Some(value.into()),
+                _ => unreachable!(),
+            }
+        }
+        Ok(config)
+    }
+
+    /// Combines this configuration, read from `tauri.conf.json`, with the values set in code
+    /// and in the environment. From lowest to highest precedence the layers are: this
+    /// configuration, the selected profile, `overrides` and `env`. The profile is chosen by
+    /// `profile` in the highest layer that sets it.
+    pub fn resolve(self, overrides: AuthiumConfig, env: AuthiumConfig) -> Result<Self> {
+        let mut base = self;
+        base.profiles.extend(overrides.profiles.clone());
+
+        let name = env.profile.clone().or_else(|| overrides.profile.clone()).or_else(|| base.profile.clone());
+        if let Some(name) = name {
+            let Some(profile) = base.profiles.get(&name).cloned() else {
+                return Err(Error::UnknownProfile(name));
+            };
+            base = base.merge(profile);
+            base.profile = Some(name);
+        }
+        Ok(base.merge(overrides).merge(env))
+    }
+
+    /// Checks that the values the plugin cannot work without are set.
+    pub fn validate(&self) -> Result<()> {
+        let required = [("apiKey", &self.api_key), ("appId", &self.app_id)];
+        for (field, value) in required {
+            if value.as_deref().is_none_or(|v| v.trim().is_empty()) {
+                return Err(Error::MissingConfig(field));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Builds the plugin. Values set here take precedence over `plugins.authium` in
+/// `tauri.conf.json` and are themselves overridden by the `AUTHIUM_*` environment variables,
+/// see [`AuthiumConfig::resolve`]. Setup fails with [`Error::MissingConfig`] if the API key or
+/// app id is set nowhere.
+///
+/// ```ignore
+/// tauri::Builder::default()
+///     .plugin(tauri_plugin_authium::Builder::new().app_id("my-app").api_key(api_key).build())
+/// ```
+#[derive(Debug, Default)]
+pub struct Builder {
+    config: AuthiumConfig,
+}
+
+impl Builder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Starts from `config` instead of an empty configuration.
+    pub fn config(mut self, config: AuthiumConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    pub fn api_key(mut self, api_key: impl Into<String>) -> Self {
+        self.config.api_key = Some(api_key.into());
+        self
+    }
+
+    pub fn app_id(mut self, app_id: impl Into<String>) -> Self {
+        self.config.app_id = Some(app_id.into());
+        self
+    }
+
+    /// Port of the local callback server used by the desktop sign-in. Defaults to 6483.
+    pub fn port(mut self, port: u16) -> Self {
+        self.config.port = Some(port);
+        self
+    }
+
+    pub fn endpoints(mut self, api_url: impl Into<String>, auth_url: impl Into<String>) -> Self {
+        self.config.api_url = Some(api_url.into());
+        self.config.auth_url = Some(auth_url.into());
+        self
+    }
+
+    /// Directory for the session files. Defaults to the app data directory.
+    pub fn store(mut self, dir: impl Into<std::path::PathBuf>) -> Self {
+        self.config.store = Some(dir.into());
+        self
+    }
+
+    pub fn callback_scheme(mut self, scheme: impl Into<String>) -> Self {
+        self.config.callback_scheme = Some(scheme.into());
+        self
+    }
+
+    /// Size and placement of the desktop sign-in window.
+    pub fn sign_in_window(mut self, window: SignInWindowConfig) -> Self {
+        self.config.sign_in_window = Some(window);
+        self
+    }
+
+    /// Only lets the given windows, by label or origin, call `command`; `"*"` applies to every
+    /// command without its own list.
+    pub fn allow_windows<I, S>(mut self, command: impl Into<String>, windows: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.config.allowed_windows.insert(command.into(), windows.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Selects an entry of `profiles`, unless `AUTHIUM_PROFILE` selects another one.
+    pub fn profile(mut self, name: impl Into<String>) -> Self {
+        self.config.profile = Some(name.into());
+        self
+    }
+
+    pub fn build<R: Runtime>(self) -> TauriPlugin<R, Option<AuthiumConfig>> {
+        let overrides = self.config;
+        PluginBuilder::<R, Option<AuthiumConfig>>::new("authium")
+            .invoke_handler(access::guard(tauri::generate_handler![
+                commands::sign_in,
+                commands::sign_in_device,
+                commands::logout,
+                commands::get_user,
+                commands::is_logged_in,
+                commands::get_auth_state,
+                commands::get_access_token,
+                commands::refresh,
+                commands::reauthenticate,
+                commands::update_user,
+                commands::update_avatar,
+                commands::get_session_info,
+                commands::start_totp_enrollment,
+                commands::confirm_totp_enrollment
+            ]))
+            .setup(move |app, api| {
+                let c = api
+                    .config()
+                    .clone()
+                    .unwrap_or_default()
+                    .resolve(overrides.clone(), AuthiumConfig::from_env()?)?;
+                c.validate()?;
+                tracing::debug!(profile = ?c.profile, "Authium configuration resolved");
+
+                let data_dir = match &c.store {
+                    Some(dir) => dir.clone(),
+                    None => app.path().app_data_dir().unwrap_or_default(),
+                };
+                *DATA_DIR.lock().unwrap() = data_dir.to_string_lossy().to_string();
+                *DAEMON_ENDPOINT.lock().unwrap() = c.api_url.clone().unwrap_or_default().trim_end_matches('/').to_string();
+                *AUTHIUM_ENDPOINT.lock().unwrap() = c.auth_url.clone().unwrap_or_default().trim_end_matches('/').to_string();
+                http::configure(&c, app.package_info())?;
+                app.manage(c.clone());
+
+                #[cfg(mobile)]
+                let authium = mobile::init(app, api)?;
+
+                #[cfg(desktop)]
+                let authium = desktop::init(app, api)?;
+                app.manage(authium);
+
+                let handle = app.app_handle();
+                let boxed_handle = Box::new(handle.clone());
+                thread::spawn(move || {
+                    setup_storage(*boxed_handle);
+                });
+
+                let boxed_handle = Box::new(handle.clone());
+                thread::spawn(move || {
+                    if let Err(e) = server::start_server(*boxed_handle, &c) {
+                        tracing::error!(error = %e, "failed to start Authium server");
+                    }
+                });
+
+                Ok(())
+            })
+            .build()
+    }
 }
 
-/// Initializes the plugin.
+/// Initializes the plugin. `config` is merged over `plugins.authium` in `tauri.conf.json`, see [`Builder`].
 pub fn init<R: Runtime>(config: Option<AuthiumConfig>) -> TauriPlugin<R, Option<AuthiumConfig>> {
-    Builder::<R, Option<AuthiumConfig>>::new("authium")
-        .invoke_handler(tauri::generate_handler![
-            commands::sign_in,
-            commands::logout,
-            commands::get_user,
-            commands::is_logged_in,
-            commands::refresh
-        ])
-        .setup(move |app, api| {
-            *DATA_DIR.lock().unwrap() = app.path().app_data_dir().unwrap_or(std::path::PathBuf::new()).to_string_lossy().to_string();
-
-            let c = config.clone().unwrap_or_else(|| api.config().clone().expect("no configuration found for authium"));
-            app.manage(c.clone());
-
-            #[cfg(mobile)]
-            let authium = mobile::init(app, api)?;
-
-            #[cfg(desktop)]
-            let authium = desktop::init(app, api)?;
-            app.manage(authium);
-
-            let handle = app.app_handle();
-            let boxed_handle = Box::new(handle.clone());
-            thread::spawn(move || {
-                setup_storage(*boxed_handle);
-            });
-            
-            let boxed_handle = Box::new(handle.clone());
-            thread::spawn(move || {
-                server::start_server(*boxed_handle, &c)
-                    .expect("Failed to start Authium server");
-            });
-
-            Ok(())
-        })
-        .build()
+    Builder::new().config(config.unwrap_or_default()).build()
 }
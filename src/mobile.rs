@@ -1,34 +1,97 @@
-use serde::de::DeserializeOwned;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use tauri::{
   plugin::{PluginApi, PluginHandle},
-  AppHandle, Runtime,
+  AppHandle, Emitter, Manager, Runtime, Url,
 };
 
-use crate::models::*;
+use crate::{
+  server::{authorize_url, complete_sign_in, rand_str, AuthorizeParams, CallbackQuery},
+  AuthiumConfig, Error, User,
+};
 
 #[cfg(target_os = "ios")]
 tauri::ios_plugin_binding!(init_plugin_authium);
 
 // initializes the Kotlin or Swift plugin classes
 pub fn init<R: Runtime, C: DeserializeOwned>(
-  _app: &AppHandle<R>,
+  app: &AppHandle<R>,
   api: PluginApi<R, C>,
 ) -> crate::Result<Authium<R>> {
   #[cfg(target_os = "android")]
-  let handle = api.register_android_plugin("", "ExamplePlugin")?;
+  let handle = api.register_android_plugin("com.ezerium.authium", "AuthiumPlugin")?;
   #[cfg(target_os = "ios")]
   let handle = api.register_ios_plugin(init_plugin_authium)?;
-  Ok(Authium(handle))
+  Ok(Authium { app: app.clone(), handle })
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SignInRequest {
+  url: String,
+  callback_scheme: String,
+}
+
+#[derive(Deserialize)]
+struct SignInResponse {
+  url: String,
 }
 
 /// Access to the authium APIs.
-pub struct Authium<R: Runtime>(PluginHandle<R>);
+pub struct Authium<R: Runtime> {
+  app: AppHandle<R>,
+  handle: PluginHandle<R>,
+}
 
 impl<R: Runtime> Authium<R> {
-  pub fn ping(&self, payload: PingRequest) -> crate::Result<PingResponse> {
-    self
-      .0
-      .run_mobile_plugin("ping", payload)
-      .map_err(Into::into)
+  /// Signs in through the platform's authentication session (`ASWebAuthenticationSession`
+  /// on iOS, Custom Tabs on Android), which hands the redirect to `callbackScheme` back to
+  /// the app. Emits `authium:login-success` once the session has started.
+  pub async fn sign_in(&self, expiry: Option<u64>) -> crate::Result<()> {
+    self.sign_in_with_acr(expiry, None).await
+  }
+
+  /// Like [`Authium::sign_in`], requesting the authentication context class `acr`, e.g. `mfa`.
+  pub async fn sign_in_with_acr(&self, expiry: Option<u64>, acr: Option<String>) -> crate::Result<()> {
+    let user = self.authorize(&AuthorizeParams { expiry, acr, ..Default::default() }, None).await?;
+    let _ = self.app.emit("authium:login-success", &user);
+    Ok(())
+  }
+
+  /// Runs the sign-in again for `user_id`, see [`Authium::reauthenticate`].
+  pub(crate) async fn authorize_again(&self, params: AuthorizeParams, user_id: i64) -> crate::Result<()> {
+    let user = self.authorize(&params, Some(user_id)).await?;
+    let _ = self.app.emit("authium:login-success", &user);
+    Ok(())
+  }
+
+  async fn authorize(&self, params: &AuthorizeParams, expected_user: Option<i64>) -> crate::Result<User> {
+    let config = self.app.state::<AuthiumConfig>().inner().clone();
+    let Some(callback_scheme) = config.callback_scheme.clone() else {
+      return Err(Error::SignIn("callbackScheme not set in configuration".into()));
+    };
+    let redirect_uri = format!("{}://authium/callback", callback_scheme);
+
+    let state = rand_str(32);
+    let url = authorize_url(&config, &state, params, Some(&redirect_uri))
+      .await
+      .map_err(|e| Error::SignIn(e.to_string()))?;
+
+    let response: SignInResponse = self
+      .handle
+      .run_mobile_plugin_async("signIn", SignInRequest { url: url.to_string(), callback_scheme })
+      .await?;
+
+    let callback = Url::parse(&response.url).map_err(|e| Error::SignIn(e.to_string()))?;
+    if let Some((_, error)) = callback.query_pairs().find(|(key, _)| key == "error") {
+      return Err(Error::SignIn(error.into_owned()));
+    }
+    let query = actix_web::web::Query::<CallbackQuery>::from_query(callback.query().unwrap_or_default())
+      .map_err(|e| Error::SignIn(e.to_string()))?;
+    if query.state != state {
+      tracing::warn!("callback state does not match the sign-in request");
+      return Err(Error::SignIn("state mismatch".into()));
+    }
+
+    complete_sign_in(&query, expected_user).await.map_err(Error::SignIn)
   }
 }
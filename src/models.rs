@@ -1,19 +1,13 @@
 use serde::{Deserialize, Serialize};
+#[cfg(any(test, feature = "ts"))]
+use ts_rs::TS;
 
-#[derive(Debug, Deserialize, Serialize)]
-#[serde(rename_all = "camelCase")]
-pub struct PingRequest {
-  pub value: Option<String>,
-}
+use crate::redact::Redacted;
 
-#[derive(Debug, Clone, Default, Deserialize, Serialize)]
-#[serde(rename_all = "camelCase")]
-pub struct PingResponse {
-  pub value: Option<String>,
-}
-
-#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+#[cfg_attr(any(test, feature = "ts"), derive(TS))]
 pub struct User {
+    #[cfg_attr(any(test, feature = "ts"), ts(type = "number"))]
     pub id: i64,
     pub username: Option<String>,
     pub email: Option<String>,
@@ -26,4 +20,180 @@ pub struct User {
     pub language: Option<String>,
     pub timezone: Option<String>,
     pub region: Option<String>,
-}
\ No newline at end of file
+
+    /// Whether the user has to confirm sign-ins with a second factor.
+    #[serde(default)]
+    pub mfa_enabled: bool,
+    /// Second factors the user has enrolled.
+    #[serde(default)]
+    pub mfa_factors: Vec<MfaFactor>,
+}
+
+/// A kind of second factor.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[cfg_attr(any(test, feature = "ts"), derive(TS))]
+#[serde(rename_all = "snake_case")]
+pub enum MfaFactor {
+    /// Codes from an authenticator app.
+    Totp,
+    Webauthn,
+    Sms,
+    /// A factor this version of the plugin does not know.
+    #[serde(other)]
+    Other,
+}
+
+/// Editable fields of the signed-in [`User`]. Fields left as `None` are not changed.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[cfg_attr(any(test, feature = "ts"), derive(TS))]
+pub struct UserUpdate {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(any(test, feature = "ts"), ts(optional))]
+    pub first_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(any(test, feature = "ts"), ts(optional))]
+    pub last_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(any(test, feature = "ts"), ts(optional))]
+    pub phone: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(any(test, feature = "ts"), ts(optional))]
+    pub language: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(any(test, feature = "ts"), ts(optional))]
+    pub timezone: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(any(test, feature = "ts"), ts(optional))]
+    pub region: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[cfg_attr(any(test, feature = "ts"), derive(TS))]
+#[serde(rename_all = "camelCase")]
+pub struct SignInPayload {
+    /// Requested session lifetime in seconds.
+    #[cfg_attr(any(test, feature = "ts"), ts(type = "number | null"))]
+    pub expiry: Option<u64>,
+    /// Authentication context class to request, e.g. `mfa` to require a second factor.
+    /// Defaults to `acrValues` in the configuration. Not supported by the device flow.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(any(test, feature = "ts"), ts(optional))]
+    pub acr: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[cfg_attr(any(test, feature = "ts"), derive(TS))]
+#[serde(rename_all = "camelCase")]
+pub struct LogoutPayload {
+    /// Ends every session of the user instead of only this one.
+    #[serde(default)]
+    pub everywhere: bool,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[cfg_attr(any(test, feature = "ts"), derive(TS))]
+#[serde(rename_all = "camelCase")]
+pub struct RefreshPayload {
+    /// Fetches the user profile again after refreshing the token.
+    #[serde(default)]
+    pub refresh_data: bool,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[cfg_attr(any(test, feature = "ts"), derive(TS))]
+#[serde(rename_all = "camelCase")]
+pub struct SessionInfoPayload {
+    /// Asks Authium whether the token is still active instead of relying on local state only.
+    #[serde(default)]
+    pub introspect: bool,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[cfg_attr(any(test, feature = "ts"), derive(TS))]
+#[serde(rename_all = "camelCase")]
+pub struct ReauthenticatePayload {
+    /// Seconds since the last sign-in after which the user has to sign in again.
+    #[cfg_attr(any(test, feature = "ts"), ts(type = "number"))]
+    pub max_age: u64,
+}
+
+/// A started TOTP enrollment. The user adds it to their authenticator app and confirms it with
+/// a code, see [`ConfirmTotpPayload`].
+#[derive(Clone, Default, Deserialize, Serialize)]
+#[cfg_attr(any(test, feature = "ts"), derive(TS))]
+#[serde(rename_all = "camelCase")]
+pub struct TotpEnrollment {
+    pub enrollment_id: String,
+    /// `otpauth://` URI to show as a QR code.
+    pub otpauth_uri: String,
+    /// The shared secret, for entering it by hand.
+    pub secret: String,
+}
+
+impl std::fmt::Debug for TotpEnrollment {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TotpEnrollment")
+            .field("enrollment_id", &self.enrollment_id)
+            .field("otpauth_uri", &Redacted(&self.otpauth_uri))
+            .field("secret", &Redacted(&self.secret))
+            .finish()
+    }
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[cfg_attr(any(test, feature = "ts"), derive(TS))]
+#[serde(rename_all = "camelCase")]
+pub struct ConfirmTotpPayload {
+    pub enrollment_id: String,
+    /// Current code from the authenticator app.
+    pub code: String,
+}
+
+/// Details about the current session. Timestamps are seconds since the Unix epoch.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[cfg_attr(any(test, feature = "ts"), derive(TS))]
+#[serde(rename_all = "camelCase")]
+pub struct SessionInfo {
+    pub logged_in: bool,
+    #[cfg_attr(any(test, feature = "ts"), ts(type = "number | null"))]
+    pub expires_at: Option<u64>,
+    #[cfg_attr(any(test, feature = "ts"), ts(type = "number | null"))]
+    pub issued_at: Option<u64>,
+    /// Unix time at which the user last signed in, as opposed to refreshing the session.
+    #[cfg_attr(any(test, feature = "ts"), ts(type = "number | null"))]
+    pub auth_time: Option<u64>,
+    pub scopes: Vec<String>,
+    pub token_type: Option<String>,
+    /// Whether the session was restored from disk on launch.
+    pub restored: bool,
+    /// Result of the server-side introspection, `None` if it was not requested.
+    pub active: Option<bool>,
+}
+
+/// User code of a pending device authorization, to be shown on screen.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[cfg_attr(any(test, feature = "ts"), derive(TS))]
+#[serde(rename_all = "camelCase")]
+pub struct DeviceCode {
+    pub user_code: String,
+    pub verification_uri: String,
+    /// Verification URL with the user code already filled in, e.g. for a QR code.
+    pub verification_uri_complete: Option<String>,
+    /// Seconds until the user code expires.
+    #[cfg_attr(any(test, feature = "ts"), ts(type = "number"))]
+    pub expires_in: u64,
+    /// Seconds between polls of the token endpoint.
+    #[cfg_attr(any(test, feature = "ts"), ts(type = "number"))]
+    pub interval: u64,
+}
+
+/// Whether a user is signed in, and who. Subscribers are notified whenever it changes.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[cfg_attr(any(test, feature = "ts"), derive(TS))]
+#[serde(tag = "status", content = "user", rename_all = "camelCase")]
+pub enum AuthState {
+    #[default]
+    SignedOut,
+    SignedIn(Box<User>),
+}
@@ -0,0 +1,29 @@
+use std::{
+    fmt,
+    hash::{DefaultHasher, Hash, Hasher},
+};
+
+/// Formats a secret such as a token, API key or CSRF state for logs.
+///
+/// Only a short fingerprint is written, which is enough to tell whether two log lines refer to
+/// the same token without revealing it. Use it for every secret-bearing tracing field, e.g.
+/// `tracing::debug!(token = %Redacted(&token))`.
+pub(crate) struct Redacted<'a>(pub &'a str);
+
+impl fmt::Display for Redacted<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.0.is_empty() {
+            return f.write_str("[empty]");
+        }
+
+        let mut hasher = DefaultHasher::new();
+        self.0.hash(&mut hasher);
+        write!(f, "[redacted:{:08x}]", hasher.finish() as u32)
+    }
+}
+
+impl fmt::Debug for Redacted<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
@@ -1,14 +1,41 @@
-use std::sync::{Arc, Mutex};
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc, Mutex,
+};
 use std::time::{Duration, SystemTime};
 use actix_web::rt::net::TcpListener;
 use actix_web::{get, web, App, HttpResponse, HttpServer, Responder};
 use lazy_static::lazy_static;
 use rand::Rng as _;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use tauri::{AppHandle, Emitter, Runtime, Url};
 
-use crate::{AuthiumConfig, AUTHIUM_ENDPOINT};
-use crate::user::{is_logged_in, login as user_login};
+use crate::{authium_endpoint, daemon_endpoint, http::RequestBuilderExt as _, redact::Redacted, AuthiumConfig, User};
+use crate::user::{is_logged_in, sign_in, IssuedTokens};
+
+/// Notified once a callback has been handled, with the user or the reason the sign-in failed.
+/// Keeps the handlers independent of the app's runtime.
+type LoginListener = Arc<dyn Fn(Result<&User, &str>) + Send + Sync>;
+
+/// Set while a sign-in window is open and its callback has not arrived yet.
+static SIGN_IN_PENDING: AtomicBool = AtomicBool::new(false);
+/// The user a pending re-authentication must sign in as.
+static EXPECTED_USER: Mutex<Option<i64>> = Mutex::new(None);
+
+/// Marks a sign-in as started, see [`cancel_sign_in`]. A re-authentication passes the
+/// signed-in user, who has to be the one signing in again.
+pub(crate) fn begin_sign_in(expected_user: Option<i64>) {
+    *EXPECTED_USER.lock().unwrap() = expected_user;
+    SIGN_IN_PENDING.store(true, Ordering::SeqCst);
+}
+
+/// Abandons the pending sign-in, so that a late callback for it is rejected. Returns whether a
+/// sign-in was still pending.
+pub(crate) fn cancel_sign_in() -> bool {
+    CSRF_TOKEN.lock().unwrap().take();
+    EXPECTED_USER.lock().unwrap().take();
+    SIGN_IN_PENDING.swap(false, Ordering::SeqCst)
+}
 
 lazy_static! {
     static ref CSRF_TOKEN: Arc<Mutex<Option<String>>> = Arc::new(Mutex::new(None));
@@ -20,92 +47,278 @@ pub async fn start_server<R: Runtime + 'static>(handle: AppHandle<R>, config: &A
     AUTH_CONFIG.lock().unwrap().replace(config.clone());
     let port = config.port.unwrap_or(6483);
 
-    let handle_data = web::Data::new(Arc::new(handle.clone()));
+    let on_login: LoginListener = Arc::new(move |result: Result<&User, &str>| {
+        let _ = match result {
+            Ok(u) => handle.emit("authium:login-success", u),
+            Err(e) => handle.emit("authium:login-failed", e),
+        };
+        #[cfg(desktop)]
+        crate::desktop::close_sign_in_window(&handle);
+    });
+    let handle_data = web::Data::new(on_login);
     match TcpListener::bind(format!("localhost:{}", port)).await {
         Ok(_) => {}
-        Err(_) => return Ok(()),
+        Err(e) => {
+            tracing::warn!(port, error = %e, "callback port is in use, not starting the Authium server");
+            return Ok(());
+        }
     }
 
+    tracing::info!(port, "starting Authium server");
+
     HttpServer::new(move || {
         App::new()
             .app_data(handle_data.clone())
             .service(login)
             .service(callback)
+            .service(error)
     })
     .bind(format!("localhost:{}", port))?
     .run()
     .await
 }
 
-#[derive(Deserialize)]
-struct LoginQuery {
-    expiry: Option<u64>,
+/// What a sign-in asks Authium for. Also the query of `/login`.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct AuthorizeParams {
+    /// Lifetime of the session in seconds.
+    pub(crate) expiry: Option<u64>,
+    /// Makes Authium ask the user to sign in again if they did not within this many seconds.
+    /// Set for re-authentication, which also passes `prompt=login`.
+    pub(crate) max_age: Option<u64>,
+    /// Authentication context class to request, `acrValues` from the configuration if unset.
+    pub(crate) acr: Option<String>,
+}
+
+impl AuthorizeParams {
+    fn prompt(&self) -> Option<&'static str> {
+        self.max_age.map(|_| "login")
+    }
 }
 
 #[get("/login")]
-async fn login(query: web::Query<LoginQuery>) -> impl Responder {
+#[tracing::instrument(
+    name = "authium.sign_in",
+    skip_all,
+    fields(expiry = ?query.expiry, max_age = ?query.max_age, acr = ?query.acr)
+)]
+async fn login(query: web::Query<AuthorizeParams>) -> impl Responder {
+    if query.max_age.is_none() && is_logged_in() {
+        tracing::debug!("already signed in");
+        SIGN_IN_PENDING.store(false, Ordering::SeqCst);
+        return HttpResponse::Ok()
+            .body("You are already logged in. You can close this window.");
+    }
+
+    let Some(config) = AUTH_CONFIG.lock().unwrap().clone() else {
+        return HttpResponse::BadRequest().body(AuthorizeError::MissingCredentials.to_string());
+    };
+
     let state = rand_str(32);
     CSRF_TOKEN.lock().unwrap().replace(state.clone());
 
-    if is_logged_in() {
-        return HttpResponse::Ok()
-            .body("You are already logged in. You can close this window.");
+    match authorize_url(&config, &state, &query, None).await {
+        Ok(url) => HttpResponse::Found()
+            .append_header(("Location", url.to_string()))
+            .finish(),
+        Err(e @ AuthorizeError::MissingCredentials) => {
+            tracing::error!("API key or App ID not set in configuration");
+            HttpResponse::BadRequest().body(e.to_string())
+        }
+        Err(e @ AuthorizeError::InvalidUrl) => HttpResponse::InternalServerError().body(e.to_string()),
+        Err(e @ AuthorizeError::Unreachable(_)) => {
+            tracing::error!(error = %e, "failed to push authorization request");
+            HttpResponse::BadGateway().body("Could not reach Authium. Please try again later.")
+        }
     }
+}
 
-    let config = AUTH_CONFIG.lock().unwrap().as_ref().unwrap().clone();
-    if (config.app_id.is_none()) || (config.api_key.is_none()) {
-        return HttpResponse::BadRequest().body("API key or App ID not set in configuration");
+#[derive(Debug, thiserror::Error)]
+pub(crate) enum AuthorizeError {
+    #[error("API key or App ID not set in configuration")]
+    MissingCredentials,
+    #[error("Invalid Authium URL in configuration")]
+    InvalidUrl,
+    #[error("failed to push authorization request: {0}")]
+    Unreachable(String),
+}
+
+/// Builds the URL of the Authium sign-in page for a sign-in identified by `state`. Authium
+/// redirects to `redirect_uri` when given, and to the app's registered callback otherwise.
+pub(crate) async fn authorize_url(
+    config: &AuthiumConfig,
+    state: &str,
+    params: &AuthorizeParams,
+    redirect_uri: Option<&str>,
+) -> Result<Url, AuthorizeError> {
+    let (Some(_), Some(app_id)) = (config.api_key.as_deref(), config.app_id.as_deref()) else {
+        return Err(AuthorizeError::MissingCredentials);
+    };
+
+    let Ok(mut url) = Url::parse(format!("{}/authorize", authium_endpoint()).as_str()) else {
+        return Err(AuthorizeError::InvalidUrl);
+    };
+    let acr = params.acr.as_deref().or(config.acr_values.as_deref());
+
+    // The API key stays on the back channel, the browser only sees the app id and a
+    // short-lived handle for the pushed request.
+    match push_authorization_request(app_id, state, params, acr, redirect_uri).await {
+        Ok(Some(request_uri)) => {
+            url.query_pairs_mut()
+                .append_pair("appId", app_id)
+                .append_pair("requestUri", &request_uri);
+        }
+        Ok(None) => {
+            url.query_pairs_mut()
+                .append_pair("appId", app_id)
+                .append_pair("state", state);
+            if let Some(expiry) = params.expiry {
+                url.query_pairs_mut().append_pair("exp", &expiry.to_string());
+            }
+            if let Some(prompt) = params.prompt() {
+                url.query_pairs_mut().append_pair("prompt", prompt);
+            }
+            if let Some(max_age) = params.max_age {
+                url.query_pairs_mut().append_pair("maxAge", &max_age.to_string());
+            }
+            if let Some(acr) = acr {
+                url.query_pairs_mut().append_pair("acrValues", acr);
+            }
+            if let Some(redirect_uri) = redirect_uri {
+                url.query_pairs_mut().append_pair("redirectUri", redirect_uri);
+            }
+        }
+        Err(e) => return Err(AuthorizeError::Unreachable(e)),
     }
 
-    let api_key = config.api_key.clone().unwrap();
-    let app_id = config.app_id.clone().unwrap();
-
-    let expiry = query.expiry.clone();
-    let mut url = Url::parse(format!("{}/authorize", AUTHIUM_ENDPOINT).as_str()).unwrap();
-    url.query_pairs_mut()
-        .append_pair("apiKey", &api_key)
-        .append_pair("appId", &app_id)
-        .append_pair("state", &state);
-    if let Some(expiry) = expiry {
-        url.query_pairs_mut().append_pair("exp", &expiry.to_string());
+    Ok(url)
+}
+
+#[derive(Serialize)]
+struct PushedAuthorizationRequest<'a> {
+    client_id: &'a str,
+    state: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    exp: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    redirect_uri: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    prompt: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_age: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    acr_values: Option<&'a str>,
+}
+
+#[derive(Deserialize)]
+struct PushedAuthorizationData {
+    request_uri: String,
+}
+
+/// Registers the authorization request with Authium (RFC 9126), authenticated with the API key.
+/// Returns `None` if Authium does not support pushed requests, in which case only public
+/// parameters are sent through the browser.
+#[tracing::instrument(name = "authium.push_authorization", skip_all, err)]
+async fn push_authorization_request(
+    app_id: &str,
+    state: &str,
+    params: &AuthorizeParams,
+    acr: Option<&str>,
+    redirect_uri: Option<&str>,
+) -> Result<Option<String>, String> {
+    let client = crate::http::client();
+    let response = client.post(format!("{}/authorize/par", daemon_endpoint()))
+        .client_auth()
+        .form(&PushedAuthorizationRequest {
+            client_id: app_id,
+            state,
+            exp: params.expiry,
+            redirect_uri,
+            prompt: params.prompt(),
+            max_age: params.max_age,
+            acr_values: acr,
+        })
+        .send_with_retry()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let status = response.status();
+    if status == reqwest::StatusCode::NOT_FOUND {
+        tracing::debug!("pushed authorization requests are not supported");
+        return Ok(None);
+    }
+    if !status.is_success() {
+        return Err(format!("Authium rejected the authorization request: {}", status));
     }
 
-    HttpResponse::Found()
-        .append_header(("Location", url.to_string()))
-        .finish()
+    let data = response.json::<PushedAuthorizationData>().await
+        .map_err(|e| format!("Failed to parse pushed authorization response: {}", e))?;
+    Ok(Some(data.request_uri))
 }
 
 #[derive(Deserialize)]
-struct CallbackQuery {
+pub(crate) struct CallbackQuery {
     access_token: String,
     refresh_token: String,
     expires_in: u64,
-    state: String,
+    pub(crate) state: String,
+    scope: Option<String>,
+    token_type: Option<String>,
+    /// Unix time at which the user authenticated, if Authium reports it.
+    auth_time: Option<u64>,
+}
+
+/// Starts the session from the tokens Authium redirected back with. The caller checks `state`.
+/// When re-authenticating, `expected_user` is the user who was signed in; if someone else signs
+/// in instead, the sign-in fails and that user stays signed in.
+pub(crate) async fn complete_sign_in(query: &CallbackQuery, expected_user: Option<i64>) -> Result<User, String> {
+    let tokens = IssuedTokens {
+        access_token: query.access_token.clone(),
+        refresh_token: query.refresh_token.clone(),
+        expiry: SystemTime::now() + Duration::from_secs(query.expires_in),
+        scope: query.scope.clone(),
+        token_type: query.token_type.clone(),
+        auth_time: query.auth_time,
+    };
+    sign_in(tokens, expected_user).await
 }
 
 #[get("/callback")]
+#[tracing::instrument(
+    name = "authium.callback",
+    skip_all,
+    fields(state = %Redacted(&query.state), expires_in = query.expires_in)
+)]
 async fn callback(
     query: web::Query<CallbackQuery>,
-    handle: web::Data<Arc<AppHandle>>,
+    on_login: web::Data<LoginListener>,
 ) -> impl Responder {
-    let access_token = query.access_token.clone();
-    let refresh_token = query.refresh_token.clone();
-    let expires_in = query.expires_in;
-    let state = query.state.clone();
-
-    if state != *CSRF_TOKEN.lock().unwrap().as_ref().unwrap() {
-        return HttpResponse::BadRequest().finish();
+    {
+        let mut token = CSRF_TOKEN.lock().unwrap();
+        if token.as_deref() != Some(query.state.as_str()) {
+            tracing::warn!("callback state does not match the sign-in request");
+            return HttpResponse::BadRequest().finish();
+        }
+        token.take();
     }
+    SIGN_IN_PENDING.store(false, Ordering::SeqCst);
+    let expected_user = EXPECTED_USER.lock().unwrap().take();
 
-    let expiry = SystemTime::now() + Duration::from_secs(expires_in);
-    if let Ok(u) = user_login(access_token, refresh_token, expiry).await {
-        handle.emit("authium:login-success", u).expect("Failed to emit login event");
-    }
+    let result = complete_sign_in(&query, expected_user).await;
+    on_login(result.as_ref().map_err(String::as_str));
 
-    CSRF_TOKEN.lock().unwrap().take();
-
-    HttpResponse::Ok()
-        .body("Authentication successful! You can close this window.")
+    match result {
+        Ok(_) => HttpResponse::Ok()
+            .body("Authentication successful! You can close this window."),
+        Err(e) => {
+            let mut location = Url::parse("http://localhost/error").expect("static URL is valid");
+            location.query_pairs_mut().append_pair("error", &e);
+            HttpResponse::Found()
+                .append_header(("Location", format!("/error?{}", location.query().unwrap_or_default())))
+                .finish()
+        }
+    }
 }
 
 #[derive(Deserialize)]
@@ -122,7 +335,7 @@ async fn error(query: web::Query<ErrorQuery>) -> impl Responder {
     ))
 }
 
-fn rand_str(len: usize) -> String {
+pub(crate) fn rand_str(len: usize) -> String {
     let mut rng = rand::rng();
     let chars = "ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789";
     let result: String = (0..len)
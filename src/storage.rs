@@ -1,91 +1,315 @@
-use std::{fs, path::Path, time::{Duration, SystemTime}};
+use std::{fs, io::Write, path::{Path, PathBuf}, time::{Duration, SystemTime}};
 
 use aes_gcm::{aead::Aead, Aes256Gcm, Key, KeyInit as _, Nonce};
+use fs4::fs_std::FileExt as _;
+use serde::{Deserialize, Serialize};
 use tauri::{async_runtime::spawn, AppHandle, Runtime, Emitter};
 
-use crate::DATA_DIR;
+use crate::{redact::Redacted, user::PendingRevocation, Error, Result, DATA_DIR};
 
 const KEY_SLICE: &[u8; 32] = b"32-byte-key-authium4141234567890";
 const NONCE_SLICE: &[u8; 12] = b"nonce-ezauth";
 
-fn create_dir_if_not_exists(path: &str) {
-    if !Path::new(path).exists() {
-        fs::create_dir_all(path).expect("Failed to create directory");
-    }
-}
+/// Files written by this version start with `MAGIC`, a format version byte, the length of the
+/// encrypted payload and its CRC-32, so a torn write is told apart from a wrong key.
+const MAGIC: &[u8; 4] = b"ATHM";
+const FORMAT_VERSION: u8 = 1;
+const HEADER_LEN: usize = MAGIC.len() + 1 + 4 + 4;
 
-fn create_file_if_not_exists(file_path: &str) {
-    if !Path::new(file_path).exists() {
-        fs::File::create(file_path).expect("Failed to create file");
+fn create_dir_if_not_exists(path: &str) -> std::io::Result<()> {
+    if !Path::new(path).exists() {
+        fs::create_dir_all(path)?;
     }
+    Ok(())
 }
 
+#[tracing::instrument(name = "authium.setup_storage", skip_all)]
 pub fn setup_storage<R: Runtime + 'static>(handle: AppHandle<R>) {
     let data_dir = DATA_DIR.lock().unwrap().clone();
 
-    create_dir_if_not_exists(&data_dir);
+    if let Err(e) = create_dir_if_not_exists(&data_dir) {
+        tracing::error!(error = %e, data_dir, "failed to create data directory");
+        return;
+    }
+
+    let lock = match lock_session() {
+        Ok(lock) => lock,
+        Err(e) => {
+            tracing::error!(error = %e, "failed to lock session storage");
+            return;
+        }
+    };
+    let session = load_session();
+    drop(lock);
 
-    let data = load_user_data();
-    if let Ok((access_token, refresh_token, expiry)) = data {
-        if !access_token.is_empty() && !refresh_token.is_empty() {
-            spawn(async move {
-                if let Ok(u) = crate::user::login(access_token, refresh_token, expiry).await {
-                    handle.emit("authium:login-success", u).expect("Failed to emit login event");
-                }
-            });
+    match session {
+        Ok(Some(record)) => {
+            if !record.access_token.is_empty() && !record.refresh_token.is_empty() {
+                tracing::debug!(version = record.version, "restoring stored session");
+                spawn(async move {
+                    if let Ok(u) = crate::user::restore(record).await {
+                        let _ = handle.emit("authium:login-success", u);
+                    }
+                });
+            }
         }
-    } else {
-        eprintln!("Failed to load user data: {}", data.err().unwrap());
+        Ok(None) => {}
+        Err(e @ Error::CorruptedStorage { .. }) => {
+            tracing::warn!(error = %e, "stored session is corrupted");
+            let _ = handle.emit("authium:storage-corrupted", e.to_string());
+        }
+        Err(e) => tracing::error!(error = %e, "failed to load user data"),
     }
+
+    spawn(crate::user::retry_pending_revocations());
 }
 
-pub fn save_user_data(access_token: &str, refresh_token: &str, expiry: SystemTime) -> std::io::Result<()> {
+/// Exclusive advisory lock on the session file, held while a process reads, refreshes and
+/// writes the session so that instances sharing the data directory never refresh with a
+/// refresh token another one already used. Released when dropped.
+pub struct SessionLock {
+    _file: fs::File,
+}
+
+/// Blocks until no other process or task holds the session lock.
+pub fn lock_session() -> std::io::Result<SessionLock> {
     let data_dir = DATA_DIR.lock().unwrap().clone();
-    let file_path = format!("{}/userdata.dat", data_dir);
-    create_file_if_not_exists(&file_path);
+    let file = fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(format!("{}/userdata.lock", data_dir))?;
+    file.lock_exclusive()?;
+    Ok(SessionLock { _file: file })
+}
 
+/// Encrypts `data` and prefixes it with the file header.
+fn seal(data: &[u8]) -> Result<Vec<u8>> {
     let key = Key::<Aes256Gcm>::from_slice(KEY_SLICE);
     let cipher = Aes256Gcm::new(key);
     let nonce = Nonce::from_slice(NONCE_SLICE);
 
-    let expiry_str = expiry.duration_since(SystemTime::UNIX_EPOCH)
-        .map(|d| d.as_secs())
-        .unwrap_or(0);
-    let data = format!("{}\n{}\n{}", access_token, refresh_token, expiry_str);
+    let encrypted_data = cipher.encrypt(nonce, data)
+        .map_err(|_| Error::Encryption)?;
 
-    let encrypted_data = cipher.encrypt(nonce, data.as_bytes())
-        .expect("Failed to encrypt user data");
+    let mut sealed = Vec::with_capacity(HEADER_LEN + encrypted_data.len());
+    sealed.extend_from_slice(MAGIC);
+    sealed.push(FORMAT_VERSION);
+    sealed.extend_from_slice(&(encrypted_data.len() as u32).to_le_bytes());
+    sealed.extend_from_slice(&crc32fast::hash(&encrypted_data).to_le_bytes());
+    sealed.extend_from_slice(&encrypted_data);
+    Ok(sealed)
+}
 
-    fs::write(file_path, encrypted_data).expect("Failed to write user data to file");
+/// Checks the file header and decrypts the payload. Files without a header were written by
+/// older versions and are decrypted as a whole.
+fn unseal(sealed: &[u8]) -> std::result::Result<Vec<u8>, &'static str> {
+    let encrypted_data = match sealed.strip_prefix(MAGIC) {
+        Some(rest) => {
+            let Some((&version, rest)) = rest.split_first() else {
+                return Err("truncated header");
+            };
+            if version != FORMAT_VERSION {
+                return Err("unsupported format version");
+            }
+            if rest.len() < 8 {
+                return Err("truncated header");
+            }
+            let (len, rest) = rest.split_at(4);
+            let (checksum, payload) = rest.split_at(4);
+            if payload.len() != u32::from_le_bytes(len.try_into().unwrap()) as usize {
+                return Err("truncated payload");
+            }
+            if crc32fast::hash(payload) != u32::from_le_bytes(checksum.try_into().unwrap()) {
+                return Err("checksum mismatch");
+            }
+            payload
+        }
+        None => sealed,
+    };
+
+    let key = Key::<Aes256Gcm>::from_slice(KEY_SLICE);
+    let cipher = Aes256Gcm::new(key);
+    let nonce = Nonce::from_slice(NONCE_SLICE);
+
+    cipher.decrypt(nonce, encrypted_data).map_err(|_| "decryption failed")
+}
+
+/// Replaces `path` with `data` without ever leaving a partially written file behind: the data
+/// goes to a temporary file next to it, is flushed to disk and then renamed over the original.
+/// On Unix the file is only readable by the current user.
+fn write_atomic(path: &Path, data: &[u8]) -> std::io::Result<()> {
+    let mut tmp_name = path.file_name().unwrap_or_default().to_os_string();
+    tmp_name.push(".tmp");
+    let tmp_path = path.with_file_name(tmp_name);
+    // a leftover from a crash may have been created with other permissions
+    let _ = fs::remove_file(&tmp_path);
+
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+
+    let result = options.open(&tmp_path).and_then(|mut file| {
+        file.write_all(data)?;
+        file.sync_all()
+    });
+    if let Err(e) = result.and_then(|_| fs::rename(&tmp_path, path)) {
+        let _ = fs::remove_file(&tmp_path);
+        return Err(e);
+    }
+
+    // make the rename itself durable
+    #[cfg(unix)]
+    if let Some(dir) = path.parent() {
+        fs::File::open(dir)?.sync_all()?;
+    }
     Ok(())
 }
 
-pub fn load_user_data() -> std::io::Result<(String, String, SystemTime)> {
+/// Version of [`SessionRecord`] written by this build.
+pub const SESSION_VERSION: u32 = 1;
+
+/// The session persisted in `userdata.dat`, serialized as JSON inside the encrypted envelope.
+///
+/// Fields added in later versions must be optional or have a default, so that older records
+/// keep loading and records written by newer builds can still be read.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SessionRecord {
+    pub version: u32,
+    pub access_token: String,
+    pub refresh_token: String,
+    /// Unix time at which the access token expires.
+    pub expires_at: u64,
+    /// Unix time at which the access token was issued.
+    #[serde(default)]
+    pub issued_at: Option<u64>,
+    /// Unix time at which the user last signed in interactively.
+    #[serde(default)]
+    pub auth_time: Option<u64>,
+    #[serde(default)]
+    pub scopes: Vec<String>,
+    #[serde(default)]
+    pub token_type: Option<String>,
+    #[serde(default)]
+    pub user_id: Option<i64>,
+    /// Identifies a sign-in across refreshes, so that processes sharing the file can tell a
+    /// session another process refreshed from a different sign-in.
+    #[serde(default)]
+    pub session_id: String,
+}
+
+impl SessionRecord {
+    /// Reads the `access\nrefresh\nexpiry` layout written before records were versioned.
+    fn from_legacy(data: &str) -> Self {
+        let mut lines = data.lines();
+        Self {
+            version: 0,
+            access_token: lines.next().unwrap_or_default().to_string(),
+            refresh_token: lines.next().unwrap_or_default().to_string(),
+            expires_at: lines.next().unwrap_or_default().parse().unwrap_or(0),
+            issued_at: None,
+            auth_time: None,
+            scopes: Vec::new(),
+            token_type: None,
+            user_id: None,
+            session_id: new_session_id(),
+        }
+    }
+
+    pub fn expiry(&self) -> SystemTime {
+        SystemTime::UNIX_EPOCH + Duration::from_secs(self.expires_at)
+    }
+}
+
+impl std::fmt::Debug for SessionRecord {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SessionRecord")
+            .field("version", &self.version)
+            .field("access_token", &Redacted(&self.access_token))
+            .field("refresh_token", &Redacted(&self.refresh_token))
+            .field("expires_at", &self.expires_at)
+            .field("issued_at", &self.issued_at)
+            .field("auth_time", &self.auth_time)
+            .field("scopes", &self.scopes)
+            .field("token_type", &self.token_type)
+            .field("user_id", &self.user_id)
+            .field("session_id", &self.session_id)
+            .finish()
+    }
+}
+
+pub fn new_session_id() -> String {
+    format!("{:032x}", rand::random::<u128>())
+}
+
+#[tracing::instrument(name = "authium.save_session", skip_all, err)]
+pub fn save_session(record: &SessionRecord) -> Result<()> {
     let data_dir = DATA_DIR.lock().unwrap().clone();
     let file_path = format!("{}/userdata.dat", data_dir);
-    if !Path::new(&file_path).exists() {
-        return Ok((String::new(), String::new(), SystemTime::now()));
-    }
 
-    let encrypted_data = fs::read(file_path).expect("Failed to read user data from file");
+    let data = serde_json::to_vec(record)?;
+    write_atomic(Path::new(&file_path), &seal(&data)?)?;
+    Ok(())
+}
 
-    let key = Key::<Aes256Gcm>::from_slice(KEY_SLICE);
-    let cipher = Aes256Gcm::new(key);
-    let nonce = Nonce::from_slice(NONCE_SLICE);
+/// Moves an unreadable session file aside so the next launch starts fresh,
+/// while keeping the file around for diagnosis.
+fn quarantine(file_path: &str, reason: &str) -> Error {
+    let timestamp = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let quarantined = PathBuf::from(format!("{}.corrupted-{}", file_path, timestamp));
+    if let Err(e) = fs::rename(file_path, &quarantined) {
+        tracing::error!(error = %e, "failed to move corrupted user data aside");
+        let _ = fs::remove_file(file_path);
+    }
+
+    Error::CorruptedStorage { path: quarantined, reason: reason.into() }
+}
 
-    let decrypted_data = cipher.decrypt(nonce, &*encrypted_data)
-        .expect("Failed to decrypt user data");
+/// Loads the stored session, if any. Sessions written in the legacy layout are migrated to
+/// the current record version on the fly, so callers should hold the [`SessionLock`].
+#[tracing::instrument(name = "authium.load_session", skip_all)]
+pub fn load_session() -> Result<Option<SessionRecord>> {
+    let data_dir = DATA_DIR.lock().unwrap().clone();
+    let file_path = format!("{}/userdata.dat", data_dir);
+    if !Path::new(&file_path).exists() {
+        return Ok(None);
+    }
 
-    let data = String::from_utf8(decrypted_data).expect("Failed to convert user data to string");
-    let mut lines = data.lines();
+    let sealed = fs::read(&file_path)?;
+    let decrypted_data = match unseal(&sealed) {
+        Ok(data) => data,
+        Err(reason) => return Err(quarantine(&file_path, reason)),
+    };
 
-    let access_token = lines.next().unwrap_or_default().to_string();
-    let refresh_token = lines.next().unwrap_or_default().to_string();
-    let expires_in = lines.next().unwrap_or_default().parse().unwrap_or(0);
+    if decrypted_data.first() == Some(&b'{') {
+        let record = match serde_json::from_slice::<SessionRecord>(&decrypted_data) {
+            Ok(record) => record,
+            Err(_) => return Err(quarantine(&file_path, "invalid session record")),
+        };
+        if record.version > SESSION_VERSION {
+            tracing::warn!(version = record.version, "session was written by a newer version");
+        }
+        return Ok(Some(record));
+    }
 
-    let expiry = SystemTime::UNIX_EPOCH + Duration::from_secs(expires_in);
+    let Ok(data) = String::from_utf8(decrypted_data) else {
+        return Err(quarantine(&file_path, "invalid UTF-8"));
+    };
+    let mut record = SessionRecord::from_legacy(&data);
+    record.version = SESSION_VERSION;
+    if let Err(e) = save_session(&record) {
+        tracing::warn!(error = %e, "failed to migrate legacy session");
+    }
+    tracing::debug!("migrated legacy session");
 
-    Ok((access_token, refresh_token, expiry))
+    Ok(Some(record))
 }
 
 pub fn clear_user_data() {
@@ -93,6 +317,38 @@ pub fn clear_user_data() {
     let file_path = format!("{}/userdata.dat", data_dir);
 
     if Path::new(&file_path).exists() {
-        fs::remove_file(file_path).expect("Failed to clear user data file");
+        if let Err(e) = fs::remove_file(file_path) {
+            tracing::error!(error = %e, "failed to clear user data file");
+        }
     }
-}
\ No newline at end of file
+}
+
+pub fn save_pending_revocations(revocations: &[PendingRevocation]) -> std::io::Result<()> {
+    let data_dir = DATA_DIR.lock().unwrap().clone();
+    let file_path = format!("{}/revocations.dat", data_dir);
+    if revocations.is_empty() {
+        if Path::new(&file_path).exists() {
+            fs::remove_file(file_path)?;
+        }
+        return Ok(());
+    }
+
+    let data = serde_json::to_vec(revocations)?;
+    let sealed = seal(&data)
+        .map_err(|_| std::io::Error::other("Failed to encrypt pending revocations"))?;
+
+    write_atomic(Path::new(&file_path), &sealed)
+}
+
+pub fn load_pending_revocations() -> Vec<PendingRevocation> {
+    let data_dir = DATA_DIR.lock().unwrap().clone();
+    let file_path = format!("{}/revocations.dat", data_dir);
+    let Ok(sealed) = fs::read(file_path) else {
+        return Vec::new();
+    };
+
+    unseal(&sealed)
+        .ok()
+        .and_then(|data| serde_json::from_slice(&data).ok())
+        .unwrap_or_default()
+}
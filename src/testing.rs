@@ -0,0 +1,422 @@
+//! In-process mock of the Authium API for integration tests, enabled by the `testing` feature.
+//!
+//! [`MockAuthium`] serves the sign-in page and the API endpoints used by the plugin on a random
+//! local port. Every route answers with a sensible success by default, and tests can script the
+//! next responses of a route with [`MockAuthium::enqueue`] to simulate expired tokens, 401s,
+//! server errors or malformed bodies.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    net::TcpListener,
+    sync::{mpsc, Arc, Mutex},
+    thread,
+    time::{Duration, SystemTime},
+};
+
+use actix_web::{dev::ServerHandle, http::Method, web, App, HttpRequest, HttpResponse, HttpServer};
+use serde_json::json;
+
+use crate::{AuthiumConfig, MfaFactor, User};
+
+pub const MOCK_API_KEY: &str = "mock-api-key";
+pub const MOCK_APP_ID: &str = "mock-app-id";
+pub const MOCK_ACCESS_TOKEN: &str = "mock-access-token";
+pub const MOCK_REFRESH_TOKEN: &str = "mock-refresh-token";
+
+/// Endpoints served by the mock.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Route {
+    /// `GET /authorize`, redirects back to the plugin's `/callback`.
+    Authorize,
+    /// `POST /v1/authorize/par`
+    PushedAuthorization,
+    /// `POST /v1/token/refresh`
+    Refresh,
+    /// `GET /v1/app/user`
+    User,
+    /// `PATCH /v1/app/user`
+    UpdateUser,
+    /// `POST /v1/user/logout`
+    Logout,
+    /// `POST /v1/token/revoke`
+    Revoke,
+    /// `POST /v1/token/introspect`
+    Introspect,
+    /// `POST /v1/device/code`
+    DeviceCode,
+    /// `POST /v1/token`
+    Token,
+    /// `POST /v1/app/user/mfa/totp`
+    TotpEnrollment,
+    /// `POST /v1/app/user/mfa/totp/confirm`
+    TotpConfirm,
+}
+
+impl Route {
+    fn resolve(method: &Method, path: &str) -> Option<Self> {
+        let route = match (method.as_str(), path) {
+            ("GET", "/authorize") => Route::Authorize,
+            ("POST", "/v1/authorize/par") => Route::PushedAuthorization,
+            ("POST", "/v1/token/refresh") => Route::Refresh,
+            ("GET", "/v1/app/user") => Route::User,
+            ("PATCH", "/v1/app/user") => Route::UpdateUser,
+            ("POST", "/v1/user/logout") => Route::Logout,
+            ("POST", "/v1/token/revoke") => Route::Revoke,
+            ("POST", "/v1/token/introspect") => Route::Introspect,
+            ("POST", "/v1/device/code") => Route::DeviceCode,
+            ("POST", "/v1/token") => Route::Token,
+            ("POST", "/v1/app/user/mfa/totp") => Route::TotpEnrollment,
+            ("POST", "/v1/app/user/mfa/totp/confirm") => Route::TotpConfirm,
+            _ => return None,
+        };
+        Some(route)
+    }
+}
+
+/// A scripted response. For [`Route::Authorize`], `{callback}` and `{state}` in the headers and
+/// body are replaced with the plugin's callback URL and the state of the request, taken from the
+/// query or from the pushed authorization request referenced by `requestUri`.
+#[derive(Debug, Clone)]
+pub struct MockResponse {
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: String,
+    delay: Option<Duration>,
+}
+
+impl MockResponse {
+    pub fn status(status: u16) -> Self {
+        Self { status, headers: Vec::new(), body: String::new(), delay: None }
+    }
+
+    pub fn json(status: u16, body: serde_json::Value) -> Self {
+        Self::status(status)
+            .with_header("Content-Type", "application/json")
+            .with_body(body.to_string())
+    }
+
+    /// A successful response whose body is not valid JSON.
+    pub fn malformed() -> Self {
+        Self::status(200)
+            .with_header("Content-Type", "application/json")
+            .with_body("{\"access_token\": ")
+    }
+
+    /// A successful token response as returned by the refresh and token endpoints.
+    pub fn token(access_token: &str, expires_in: u64) -> Self {
+        Self::json(200, json!({
+            "access_token": access_token,
+            "expires_in": expires_in,
+            "token_type": "Bearer",
+        }))
+    }
+
+    /// A successful refresh response that also rotates the refresh token.
+    pub fn rotated(access_token: &str, refresh_token: &str, expires_in: u64) -> Self {
+        Self::json(200, json!({
+            "access_token": access_token,
+            "refresh_token": refresh_token,
+            "expires_in": expires_in,
+            "token_type": "Bearer",
+        }))
+    }
+
+    /// An OAuth error response, e.g. `invalid_grant`.
+    pub fn oauth_error(status: u16, error: &str) -> Self {
+        Self::json(status, json!({ "error": error }))
+    }
+
+    pub fn user(user: &User) -> Self {
+        Self::json(200, serde_json::to_value(user).unwrap())
+    }
+
+    /// Redirects the sign-in page back to the plugin with the given tokens.
+    pub fn authorized(access_token: &str, refresh_token: &str, expires_in: u64) -> Self {
+        let location = format!(
+            "{{callback}}?access_token={}&refresh_token={}&expires_in={}&state={{state}}",
+            access_token, refresh_token, expires_in
+        );
+        Self::status(302).with_header("Location", &location)
+    }
+
+    /// Like [`MockResponse::authorized`], reporting that the user authenticated at `auth_time`
+    /// in Unix seconds.
+    pub fn authorized_at(access_token: &str, refresh_token: &str, expires_in: u64, auth_time: u64) -> Self {
+        let location = format!(
+            "{{callback}}?access_token={}&refresh_token={}&expires_in={}&auth_time={}&state={{state}}",
+            access_token, refresh_token, expires_in, auth_time
+        );
+        Self::status(302).with_header("Location", &location)
+    }
+
+    pub fn with_header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    pub fn with_body(mut self, body: impl Into<String>) -> Self {
+        self.body = body.into();
+        self
+    }
+
+    /// Holds the response back for `delay`, e.g. to let concurrent requests overlap.
+    pub fn with_delay(mut self, delay: Duration) -> Self {
+        self.delay = Some(delay);
+        self
+    }
+}
+
+/// A request received by the mock.
+#[derive(Debug, Clone)]
+pub struct RecordedRequest {
+    pub query: String,
+    pub headers: HashMap<String, String>,
+    pub body: String,
+}
+
+#[derive(Default)]
+struct MockState {
+    callback: String,
+    scripted: HashMap<Route, VecDeque<MockResponse>>,
+    requests: HashMap<Route, Vec<RecordedRequest>>,
+    /// `request_uri` to `state` of pushed authorization requests
+    pushed: HashMap<String, String>,
+    refreshes: usize,
+}
+
+impl MockState {
+    fn default_response(&mut self, route: Route) -> MockResponse {
+        match route {
+            Route::Authorize => MockResponse::authorized(MOCK_ACCESS_TOKEN, MOCK_REFRESH_TOKEN, 3600),
+            Route::PushedAuthorization => MockResponse::json(201, json!({
+                "request_uri": format!("urn:authium:request:{}", self.pushed.len() + 1),
+                "expires_in": 60,
+            })),
+            Route::Refresh => {
+                self.refreshes += 1;
+                MockResponse::token(&format!("{}-{}", MOCK_ACCESS_TOKEN, self.refreshes), 3600)
+            }
+            Route::Token => MockResponse::rotated(MOCK_ACCESS_TOKEN, MOCK_REFRESH_TOKEN, 3600),
+            Route::User | Route::UpdateUser => MockResponse::user(&mock_user()),
+            Route::Introspect => MockResponse::json(200, json!({ "active": true })),
+            Route::DeviceCode => MockResponse::json(200, json!({
+                "device_code": "mock-device-code",
+                "user_code": "MOCK-CODE",
+                "verification_uri": "https://authium.example/device",
+                "expires_in": 600,
+                "interval": 1,
+            })),
+            Route::Logout | Route::Revoke => MockResponse::json(200, json!({})),
+            Route::TotpEnrollment => MockResponse::json(200, json!({
+                "enrollment_id": "mock-enrollment",
+                "otpauth_uri": "otpauth://totp/Authium:mock?secret=MOCKSECRET&issuer=Authium",
+                "secret": "MOCKSECRET",
+            })),
+            Route::TotpConfirm => MockResponse::user(&User {
+                mfa_enabled: true,
+                mfa_factors: vec![MfaFactor::Totp],
+                ..mock_user()
+            }),
+        }
+    }
+}
+
+/// The user returned by [`Route::User`] unless another response is scripted.
+pub fn mock_user() -> User {
+    User {
+        id: 1,
+        username: Some("mock".into()),
+        email: Some("mock@authium.example".into()),
+        first_name: Some("Mock".into()),
+        last_name: Some("User".into()),
+        language: Some("en".into()),
+        ..Default::default()
+    }
+}
+
+/// Destroys the open sign-in window as if the user closed it. The mock runtime does not deliver
+/// window events, so this also runs what the plugin does once the window is gone.
+#[cfg(desktop)]
+pub fn destroy_sign_in_window<R: tauri::Runtime>(app: &tauri::AppHandle<R>) {
+    crate::desktop::destroy_sign_in_window(app);
+}
+
+/// Expires the access token held in memory, so its next use refreshes the session.
+pub fn expire_access_token() {
+    crate::user::expire_access_token();
+}
+
+/// Rewrites the stored session as another process sharing the data directory does after it
+/// refreshed the same sign-in.
+pub fn refresh_stored_session(access_token: &str, refresh_token: &str, expires_in: u64) {
+    let _lock = crate::storage::lock_session().expect("failed to lock the stored session");
+    let mut record = crate::storage::load_session()
+        .expect("failed to read the stored session")
+        .expect("no session is stored");
+    let now = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap().as_secs();
+    record.access_token = access_token.into();
+    record.refresh_token = refresh_token.into();
+    record.expires_at = now + expires_in;
+    record.issued_at = Some(now);
+    crate::storage::save_session(&record).expect("failed to store the session");
+}
+
+/// A running mock Authium server. The server is stopped when this is dropped.
+pub struct MockAuthium {
+    url: String,
+    port: u16,
+    state: Arc<Mutex<MockState>>,
+    handle: ServerHandle,
+}
+
+impl MockAuthium {
+    pub fn start() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("failed to bind mock Authium server");
+        let url = format!("http://127.0.0.1:{}", listener.local_addr().unwrap().port());
+
+        // the port the plugin's own callback server will listen on
+        let port = TcpListener::bind("127.0.0.1:0")
+            .and_then(|l| l.local_addr())
+            .expect("failed to reserve a callback port")
+            .port();
+
+        let state = Arc::new(Mutex::new(MockState {
+            callback: format!("http://localhost:{}/callback", port),
+            ..Default::default()
+        }));
+
+        let data = web::Data::new(state.clone());
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            actix_web::rt::System::new().block_on(async move {
+                let server = HttpServer::new(move || {
+                    App::new()
+                        .app_data(data.clone())
+                        .default_service(web::to(respond))
+                })
+                .workers(1)
+                .listen(listener)
+                .expect("failed to start mock Authium server")
+                .run();
+
+                tx.send(server.handle()).unwrap();
+                server.await
+            })
+        });
+
+        let handle = rx.recv().expect("mock Authium server did not start");
+        Self { url, port, state, handle }
+    }
+
+    /// Base URL of the mock, serving the sign-in pages.
+    pub fn url(&self) -> String {
+        self.url.clone()
+    }
+
+    /// Base URL of the mock API.
+    pub fn api_url(&self) -> String {
+        format!("{}/v1", self.url)
+    }
+
+    /// A plugin configuration pointing at this mock.
+    pub fn config(&self) -> AuthiumConfig {
+        AuthiumConfig::new(MOCK_API_KEY.into(), MOCK_APP_ID.into())
+            .with_port(self.port)
+            .with_endpoints(self.api_url(), self.url())
+    }
+
+    /// Queues `response` as the next answer of `route`. Responses are used in the order they were queued.
+    pub fn enqueue(&self, route: Route, response: MockResponse) {
+        let mut state = self.state.lock().unwrap();
+        state.scripted.entry(route).or_default().push_back(response);
+    }
+
+    /// Requests received on `route` so far.
+    pub fn requests(&self, route: Route) -> Vec<RecordedRequest> {
+        let state = self.state.lock().unwrap();
+        state.requests.get(&route).cloned().unwrap_or_default()
+    }
+
+    /// Forgets scripted responses and recorded requests and restarts the issued token numbering.
+    pub fn reset(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.scripted.clear();
+        state.requests.clear();
+        state.pushed.clear();
+        state.refreshes = 0;
+    }
+}
+
+impl Drop for MockAuthium {
+    fn drop(&mut self) {
+        let _ = self.handle.stop(false);
+    }
+}
+
+async fn respond(req: HttpRequest, body: web::Bytes, state: web::Data<Arc<Mutex<MockState>>>) -> HttpResponse {
+    let Some(route) = Route::resolve(req.method(), req.path()) else {
+        return HttpResponse::NotFound().finish();
+    };
+
+    let recorded = RecordedRequest {
+        query: req.query_string().to_string(),
+        headers: req.headers()
+            .iter()
+            .map(|(k, v)| (k.as_str().to_string(), v.to_str().unwrap_or_default().to_string()))
+            .collect(),
+        body: String::from_utf8_lossy(&body).to_string(),
+    };
+
+    let query = web::Query::<HashMap<String, String>>::from_query(req.query_string())
+        .map(|q| q.into_inner())
+        .unwrap_or_default();
+
+    let (response, callback, authorize_state) = {
+        let mut state = state.lock().unwrap();
+        state.requests.entry(route).or_default().push(recorded);
+        let scripted = state.scripted.get_mut(&route).and_then(|q| q.pop_front());
+        let response = match scripted {
+            Some(response) => response,
+            None => state.default_response(route),
+        };
+
+        if route == Route::PushedAuthorization {
+            let request_uri = serde_json::from_str::<serde_json::Value>(&response.body)
+                .ok()
+                .and_then(|v| v["request_uri"].as_str().map(String::from));
+            let form = web::Query::<HashMap<String, String>>::from_query(&String::from_utf8_lossy(&body))
+                .map(|q| q.into_inner())
+                .unwrap_or_default();
+            if let (Some(request_uri), Some(pushed_state)) = (request_uri, form.get("state")) {
+                state.pushed.insert(request_uri, pushed_state.clone());
+            }
+        }
+
+        let authorize_state = match query.get("requestUri") {
+            Some(request_uri) => state.pushed.get(request_uri).cloned(),
+            None => query.get("state").cloned(),
+        };
+        (response, state.callback.clone(), authorize_state.unwrap_or_default())
+    };
+
+    let substitute = |value: &str| {
+        if route != Route::Authorize {
+            return value.to_string();
+        }
+        value
+            .replace("{callback}", &callback)
+            .replace("{state}", &authorize_state)
+    };
+
+    if let Some(delay) = response.delay {
+        actix_web::rt::time::sleep(delay).await;
+    }
+
+    let status = actix_web::http::StatusCode::from_u16(response.status)
+        .unwrap_or(actix_web::http::StatusCode::INTERNAL_SERVER_ERROR);
+    let mut builder = HttpResponse::build(status);
+    for (name, value) in &response.headers {
+        builder.insert_header((name.as_str(), substitute(value)));
+    }
+    builder.body(substitute(&response.body))
+}
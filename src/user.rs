@@ -1,117 +1,385 @@
 use lazy_static::lazy_static;
-use once_cell::sync::{Lazy, OnceCell};
+use once_cell::sync::Lazy;
 use serde::{Deserialize, Serialize};
-use tauri::{AppHandle, Runtime};
-use std::{sync::{Arc, Mutex}, time::{Duration, SystemTime}};
-use reqwest::{Client, Response, Url};
+use std::{sync::{atomic::{AtomicBool, Ordering}, Arc, Mutex}, time::{Duration, SystemTime}};
+use reqwest::{multipart, Client, Response, StatusCode};
+use tokio::sync::watch;
 
-use crate::{storage::{clear_user_data, save_user_data}, User, DAEMON_ENDPOINT};
+use crate::{http::RequestBuilderExt as _, redact::Redacted, storage::{clear_user_data, load_pending_revocations, load_session, lock_session, new_session_id, save_pending_revocations, save_session, SessionLock, SessionRecord, SESSION_VERSION}, daemon_endpoint, AuthState, SessionInfo, TotpEnrollment, User, UserUpdate};
 
 pub static USER: Lazy<Mutex<Option<User>>> = Lazy::new(|| Mutex::new(None));
 static EXPIRY: Lazy<Mutex<SystemTime>> = Lazy::new(|| Mutex::new(SystemTime::now()));
+static ISSUED_AT: Lazy<Mutex<Option<SystemTime>>> = Lazy::new(|| Mutex::new(None));
+/// When the user last proved their presence by signing in, as opposed to refreshing.
+static AUTH_TIME: Lazy<Mutex<Option<SystemTime>>> = Lazy::new(|| Mutex::new(None));
+static SCOPES: Lazy<Mutex<Vec<String>>> = Lazy::new(|| Mutex::new(Vec::new()));
+static TOKEN_TYPE: Lazy<Mutex<Option<String>>> = Lazy::new(|| Mutex::new(None));
+static SESSION_ID: Lazy<Mutex<String>> = Lazy::new(|| Mutex::new(String::new()));
+static RESTORED: AtomicBool = AtomicBool::new(false);
+static AUTH_STATE: Lazy<watch::Sender<AuthState>> = Lazy::new(|| watch::channel(AuthState::SignedOut).0);
+/// Outcome of the refresh currently talking to Authium, shared with every caller that asks for
+/// a refresh while it runs.
+static REFRESH_IN_FLIGHT: Lazy<Mutex<Option<watch::Receiver<Option<Result<(), String>>>>>> = Lazy::new(|| Mutex::new(None));
 
 lazy_static! {
     static ref ACCESS_TOKEN: Arc<Mutex<Option<String>>> = Arc::new(Mutex::new(None));
     static ref REFRESH_TOKEN: Arc<Mutex<Option<String>>> = Arc::new(Mutex::new(None));
 }
 
-pub async fn login(mut access_token: String, refresh_token: String, mut expiry: SystemTime) -> Result<User, String> {
+/// Starts a session from the given tokens. `restored` is set when the tokens were loaded from disk
+/// rather than obtained from a fresh sign-in.
+pub async fn login(access_token: String, refresh_token: String, expiry: SystemTime, restored: bool) -> Result<User, String> {
+    start_session(access_token, refresh_token, expiry, restored, || {}).await
+}
+
+/// Tokens Authium issued at the end of an interactive sign-in.
+pub(crate) struct IssuedTokens {
+    pub access_token: String,
+    pub refresh_token: String,
+    pub expiry: SystemTime,
+    pub scope: Option<String>,
+    pub token_type: Option<String>,
+    /// Unix time at which the user authenticated, if Authium reported it.
+    pub auth_time: Option<u64>,
+}
+
+/// Starts the session from an interactive sign-in and revokes the tokens of the session it
+/// replaces.
+///
+/// When re-authenticating, `expected_user` is the user who is signed in. The new tokens are
+/// checked against it before anything changes, so if someone else signs in instead the current
+/// session is kept and the new tokens are revoked.
+#[tracing::instrument(name = "authium.sign_in", skip_all, fields(expected_user = ?expected_user), err)]
+pub(crate) async fn sign_in(tokens: IssuedTokens, expected_user: Option<i64>) -> Result<User, String> {
+    let IssuedTokens { access_token, refresh_token, expiry, scope, token_type, auth_time } = tokens;
+
+    if let Some(expected) = expected_user {
+        let user = token_owner(access_token.clone()).await?;
+        if user.id != expected {
+            tracing::warn!(expected, user_id = user.id, "re-authenticated as a different user");
+            revoke_all(vec![
+                PendingRevocation::Token { token: refresh_token, token_type_hint: "refresh_token".into() },
+                PendingRevocation::Token { token: access_token, token_type_hint: "access_token".into() },
+            ]).await;
+            return Err("Signed in as a different user".into());
+        }
+    }
+
+    let mut replaced = Vec::new();
+    if let Some(token) = REFRESH_TOKEN.lock().unwrap().clone().filter(|t| *t != refresh_token) {
+        replaced.push(PendingRevocation::Token { token, token_type_hint: "refresh_token".into() });
+    }
+    if let Some(token) = ACCESS_TOKEN.lock().unwrap().clone().filter(|t| *t != access_token) {
+        replaced.push(PendingRevocation::Token { token, token_type_hint: "access_token".into() });
+    }
+
+    let user = start_session(access_token, refresh_token, expiry, false, || {
+        set_token_details(scope.as_deref(), token_type);
+        set_auth_time(auth_time);
+    }).await?;
+    revoke_all(replaced).await;
+    Ok(user)
+}
+
+/// Shared by [`login`] and [`sign_in`]. `details` records what Authium said about the tokens once
+/// they are known to work, right before the session is published and saved.
+#[tracing::instrument(
+    name = "authium.login",
+    skip_all,
+    fields(restored = restored, access_token = %Redacted(&access_token), refresh_token = %Redacted(&refresh_token)),
+    err
+)]
+async fn start_session(
+    mut access_token: String,
+    refresh_token: String,
+    expiry: SystemTime,
+    restored: bool,
+    details: impl FnOnce(),
+) -> Result<User, String> {
     *EXPIRY.lock().unwrap() = expiry;
+    RESTORED.store(restored, Ordering::Relaxed);
+    if !restored {
+        ISSUED_AT.lock().unwrap().replace(SystemTime::now());
+        *SESSION_ID.lock().unwrap() = new_session_id();
+    }
     ACCESS_TOKEN.lock().unwrap().replace(access_token.clone());
-    REFRESH_TOKEN.lock().unwrap().replace(refresh_token.clone());
+    REFRESH_TOKEN.lock().unwrap().replace(refresh_token);
 
-    if let Err(e) = refresh(refresh_token.clone()).await {
-        return Err("Failed to refresh token".into());
-    }
-    
-    access_token = ACCESS_TOKEN.lock().unwrap().clone().unwrap();
-    expiry = *EXPIRY.lock().unwrap();
-    if let Err(_) = save_user_data(access_token.as_str(), refresh_token.as_str(), expiry) {
-        return Err("Failed to save user data".into());
+    if let Err(e) = refresh().await {
+        return Err(format!("Failed to refresh token: {}", e));
     }
 
-    let user = fetch_user_data(access_token).await;
-    if let Ok(u) = user {
-        *USER.lock().unwrap() = Some(u.clone());
-        Ok(u)
-    } else {
-        eprintln!("Failed to fetch user data: {}", user.err().unwrap());
-        Err("Failed to fetch user data".into())
+    let Some(token) = ACCESS_TOKEN.lock().unwrap().clone() else {
+        return Err("Session was cleared during sign-in".into());
+    };
+    access_token = token;
+
+    let Ok(u) = fetch_user_data(access_token).await else {
+        return Err("Failed to fetch user data".into());
+    };
+    details();
+    set_user(u.clone());
+    let _lock = lock_storage().await?;
+    if let Err(e) = persist() {
+        return Err(format!("Failed to save user data: {}", e));
     }
+    tracing::info!(user_id = u.id, "signed in");
+    Ok(u)
+}
+
+/// Restores a session loaded from disk, including the token details that were stored with it.
+pub(crate) async fn restore(record: SessionRecord) -> Result<User, String> {
+    *ISSUED_AT.lock().unwrap() = record.issued_at.map(from_unix_secs);
+    *AUTH_TIME.lock().unwrap() = record.auth_time.map(from_unix_secs);
+    *SCOPES.lock().unwrap() = record.scopes.clone();
+    *TOKEN_TYPE.lock().unwrap() = record.token_type.clone();
+    *SESSION_ID.lock().unwrap() = if record.session_id.is_empty() { new_session_id() } else { record.session_id.clone() };
+    let expiry = record.expiry();
+    login(record.access_token, record.refresh_token, expiry, true).await
 }
 
+/// Takes the cross-process session lock without blocking the async runtime.
+async fn lock_storage() -> Result<SessionLock, String> {
+    tauri::async_runtime::spawn_blocking(lock_session)
+        .await
+        .map_err(|e| format!("Failed to lock session storage: {}", e))?
+        .map_err(|e| format!("Failed to lock session storage: {}", e))
+}
+
+/// Writes the current session to disk. Callers hold the session lock.
+fn persist() -> crate::Result<()> {
+    let record = SessionRecord {
+        version: SESSION_VERSION,
+        access_token: ACCESS_TOKEN.lock().unwrap().clone().unwrap_or_default(),
+        refresh_token: REFRESH_TOKEN.lock().unwrap().clone().unwrap_or_default(),
+        expires_at: unix_secs(*EXPIRY.lock().unwrap()),
+        issued_at: ISSUED_AT.lock().unwrap().map(unix_secs),
+        auth_time: AUTH_TIME.lock().unwrap().map(unix_secs),
+        scopes: SCOPES.lock().unwrap().clone(),
+        token_type: TOKEN_TYPE.lock().unwrap().clone(),
+        user_id: USER.lock().unwrap().as_ref().map(|u| u.id),
+        session_id: SESSION_ID.lock().unwrap().clone(),
+    };
+    save_session(&record)
+}
+
+#[tracing::instrument(name = "authium.fetch_user", skip_all, fields(access_token = %Redacted(&token)), err)]
 async fn fetch_user_data(token: String) -> Result<User, String> {
-    let client = Client::new();
-    let _res = client.get(format!("{}/app/user", DAEMON_ENDPOINT))
-        .bearer_auth(token)
-        .send()
-        .await;
-    if let Ok(response) = _res {
-        if response.status().is_success() {
-            let body = response.json::<User>().await.unwrap();
-            return Ok(body);
-        } else {
-            clear();
-            return Err(format!("Failed to fetch user data: {}", response.status()).to_string());
-        }
+    let mut response = request_user(token).await?;
+    if response.status() == StatusCode::UNAUTHORIZED {
+        // the access token may have been revoked before it expired, a refresh tells
+        tracing::debug!("access token was rejected, refreshing");
+        *EXPIRY.lock().unwrap() = SystemTime::now();
+        refresh().await?;
+
+        let token = ACCESS_TOKEN.lock().unwrap().clone().unwrap_or_default();
+        response = request_user(token).await?;
+    }
+
+    let status = response.status();
+    if status == StatusCode::UNAUTHORIZED {
+        clear().await;
+    }
+    if !status.is_success() {
+        return Err(format!("Failed to fetch user data: {}", status));
     }
 
-    clear();
-    Err("Failed to fetch user data".into())
+    response.json::<User>().await
+        .map_err(|e| format!("Failed to parse user data: {}", e))
 }
 
-#[derive(Debug, Deserialize)]
+/// Fetches the user an access token belongs to, without touching the session.
+async fn token_owner(access_token: String) -> Result<User, String> {
+    let response = request_user(access_token).await?;
+    let status = response.status();
+    if !status.is_success() {
+        return Err(format!("Failed to fetch user data: {}", status));
+    }
+
+    response.json::<User>().await
+        .map_err(|e| format!("Failed to parse user data: {}", e))
+}
+
+async fn request_user(token: String) -> Result<Response, String> {
+    let client = crate::http::client();
+    client.get(format!("{}/app/user", daemon_endpoint()))
+        .bearer_auth(token)
+        .send_with_retry()
+        .await
+        .map_err(|e| format!("Failed to fetch user data: {}", e))
+}
+
+#[derive(Deserialize)]
 struct RefreshData {
     access_token: String,
+    /// Set when Authium rotates refresh tokens; the previous one is then no longer valid.
+    refresh_token: Option<String>,
     expires_in: u64,
+    scope: Option<String>,
+    token_type: Option<String>,
 }
 
-#[derive(Debug, Serialize)]
+/// Returned by Authium when a refresh token that was already rotated away is presented again.
+/// Authium revokes every token of the session in that case.
+const REFRESH_TOKEN_REUSED: &str = "refresh_token_reused";
+
+#[derive(Serialize)]
 struct RefreshRequest {
     refresh_token: String,
 }
 
-async fn refresh(refresh_token: String) -> Result<(), String> {
+/// Clears the in-flight refresh when the refresh that registered it finishes or is dropped.
+struct InFlightRefresh;
+
+impl Drop for InFlightRefresh {
+    fn drop(&mut self) {
+        REFRESH_IN_FLIGHT.lock().unwrap().take();
+    }
+}
+
+/// Whether the access token expired and has to be refreshed before it is used.
+fn access_token_expired() -> bool {
     let expiry = *EXPIRY.lock().unwrap();
     let diff = expiry.duration_since(SystemTime::now()).unwrap_or_default();
     if diff > Duration::from_secs(0) {
+        tracing::trace!(expires_in = diff.as_secs(), "access token still valid");
+        return false;
+    }
+    true
+}
+
+/// Expires the access token held in memory, see [`crate::testing::expire_access_token`].
+#[cfg(feature = "testing")]
+pub(crate) fn expire_access_token() {
+    *EXPIRY.lock().unwrap() = SystemTime::UNIX_EPOCH;
+}
+
+/// Refreshes the access token if it expired. Concurrent callers share a single request to Authium.
+#[tracing::instrument(name = "authium.refresh", skip_all, err)]
+async fn refresh() -> Result<(), String> {
+    if !access_token_expired() {
         return Ok(());
     }
 
+    let (sender, mut receiver) = {
+        let mut in_flight = REFRESH_IN_FLIGHT.lock().unwrap();
+        match in_flight.as_ref() {
+            Some(receiver) => (None, receiver.clone()),
+            None => {
+                let (sender, receiver) = watch::channel(None);
+                *in_flight = Some(receiver.clone());
+                (Some(sender), receiver)
+            }
+        }
+    };
+
+    let Some(sender) = sender else {
+        tracing::trace!("waiting for in-flight refresh");
+        return match receiver.wait_for(Option::is_some).await {
+            Ok(outcome) => outcome.clone().unwrap_or(Ok(())),
+            Err(_) => Err("Refresh was cancelled".into()),
+        };
+    };
+
+    let _in_flight = InFlightRefresh;
+    // a refresh that finished between the check above and taking the slot already rotated the
+    // refresh token, refreshing again would present the old one
+    let result = if access_token_expired() { refresh_token_now().await } else { Ok(()) };
+    let _ = sender.send(Some(result.clone()));
+    result
+}
+
+/// Refreshes the access token with the current refresh token, or the one another process
+/// rotated it to.
+async fn refresh_token_now() -> Result<(), String> {
+    let _lock = lock_storage().await?;
+    let refresh_token = match adopt_stored_session() {
+        Some(record) if record.expiry() > SystemTime::now() => {
+            tracing::debug!("access token was refreshed by another process");
+            return Ok(());
+        }
+        Some(record) => record.refresh_token,
+        None => REFRESH_TOKEN.lock().unwrap().clone().ok_or("Not logged in")?,
+    };
+
     let body = &RefreshRequest {
         refresh_token
     };
-    let client = Client::new();
-    let _res = client.post(format!("{}/token/refresh", DAEMON_ENDPOINT))
+    let client = crate::http::client();
+    let response = client.post(format!("{}/token/refresh", daemon_endpoint()))
         .json(body)
-        .send()
-        .await;
+        .send_with_retry()
+        .await
+        .map_err(|e| format!("Failed to refresh token: {}", e))?;
 
-    if let Ok(response) = _res {
-        let status = response.status();
-        if status.is_success() {
-            let body = response.json::<RefreshData>().await.unwrap();
+    let status = response.status();
+    if status.is_success() {
+        let body = response.json::<RefreshData>().await
+            .map_err(|e| format!("Failed to parse refresh response: {}", e))?;
 
-            ACCESS_TOKEN.lock().unwrap().replace(body.access_token);
-            *EXPIRY.lock().unwrap() = SystemTime::now() + Duration::from_secs(body.expires_in);
-            return Ok(());
-        } else {
-            let body: serde_json::Value = response.json().await.unwrap_or_default();
-            clear();
-            return Err(format!("{} - {}", status, body).into());
+        tracing::debug!(access_token = %Redacted(&body.access_token), expires_in = body.expires_in, "access token refreshed");
+        ACCESS_TOKEN.lock().unwrap().replace(body.access_token);
+        if let Some(refresh_token) = body.refresh_token {
+            tracing::debug!(refresh_token = %Redacted(&refresh_token), "refresh token rotated");
+            REFRESH_TOKEN.lock().unwrap().replace(refresh_token);
         }
-    } else {
-        clear();
-        return Err("Failed to refresh token".into());
+        *EXPIRY.lock().unwrap() = SystemTime::now() + Duration::from_secs(body.expires_in);
+        ISSUED_AT.lock().unwrap().replace(SystemTime::now());
+        set_token_details(body.scope.as_deref(), body.token_type);
+        // a rotated refresh token is all we have left, so it has to reach the disk right away
+        if let Err(e) = persist() {
+            tracing::warn!(error = %e, "failed to save refreshed session");
+        }
+        return Ok(());
+    }
+
+    // only a refresh token Authium no longer accepts ends the session, anything else may pass
+    let body: serde_json::Value = response.json().await.unwrap_or_default();
+    if body["error"] == REFRESH_TOKEN_REUSED {
+        tracing::warn!("refresh token reuse detected, session was revoked");
+        clear_locked();
+        return Err("Session was revoked because its refresh token was reused".into());
+    }
+    if status == StatusCode::UNAUTHORIZED || body["error"] == "invalid_grant" {
+        clear_locked();
     }
+    Err(format!("{} - {}", status, body))
+}
+
+/// Takes over the stored session when another process sharing the data directory refreshed
+/// the same sign-in since we last saw it, so we never refresh with a token it already used.
+fn adopt_stored_session() -> Option<SessionRecord> {
+    let record = match load_session() {
+        Ok(Some(record)) => record,
+        Ok(None) => return None,
+        Err(e) => {
+            tracing::warn!(error = %e, "failed to read stored session");
+            return None;
+        }
+    };
+    if record.session_id.is_empty() || record.session_id != *SESSION_ID.lock().unwrap() {
+        return None;
+    }
+    if ACCESS_TOKEN.lock().unwrap().as_deref() == Some(record.access_token.as_str()) {
+        return None;
+    }
+
+    ACCESS_TOKEN.lock().unwrap().replace(record.access_token.clone());
+    REFRESH_TOKEN.lock().unwrap().replace(record.refresh_token.clone());
+    *EXPIRY.lock().unwrap() = record.expiry();
+    *ISSUED_AT.lock().unwrap() = record.issued_at.map(from_unix_secs);
+    *AUTH_TIME.lock().unwrap() = record.auth_time.map(from_unix_secs);
+    *SCOPES.lock().unwrap() = record.scopes.clone();
+    *TOKEN_TYPE.lock().unwrap() = record.token_type.clone();
+    Some(record)
 }
 
 pub async fn refresh_user(refresh_data: bool) -> Result<(), String> {
-    let token = REFRESH_TOKEN.lock().unwrap().clone().unwrap_or_default();
-    refresh(token).await.expect("Failed to refresh token");
+    refresh().await?;
     if refresh_data {
-        if let Ok(u) = fetch_user_data(ACCESS_TOKEN.lock().unwrap().clone().unwrap_or_default()).await {
-            *USER.lock().unwrap() = Some(u.clone());
+        let access_token = ACCESS_TOKEN.lock().unwrap().clone().unwrap_or_default();
+        if let Ok(u) = fetch_user_data(access_token).await {
+            set_user(u.clone());
             Ok(())
         } else {
             Err("Failed to fetch user data".into())
@@ -121,32 +389,543 @@ pub async fn refresh_user(refresh_data: bool) -> Result<(), String> {
     }
 }
 
-pub fn get_user() -> Option<User> {
-    let _ = refresh(REFRESH_TOKEN.lock().unwrap().clone().unwrap_or_default());
+const MAX_NAME_LENGTH: usize = 64;
+const MAX_AVATAR_SIZE: usize = 5 * 1024 * 1024;
+const AVATAR_MIME_TYPES: &[&str] = &["image/png", "image/jpeg", "image/webp", "image/gif"];
+
+fn validate_update(update: &UserUpdate) -> Result<(), String> {
+    for (field, value) in [("first_name", &update.first_name), ("last_name", &update.last_name)] {
+        if let Some(value) = value {
+            if value.trim().is_empty() || value.chars().count() > MAX_NAME_LENGTH {
+                return Err(format!("{} must be between 1 and {} characters", field, MAX_NAME_LENGTH));
+            }
+        }
+    }
+
+    if let Some(phone) = &update.phone {
+        let digits: String = phone.chars().filter(|c| !matches!(c, ' ' | '-' | '(' | ')')).collect();
+        let valid = digits.strip_prefix('+')
+            .is_some_and(|d| (7..=15).contains(&d.len()) && d.chars().all(|c| c.is_ascii_digit()));
+        if !valid {
+            return Err("phone must be in international format, e.g. +4512345678".into());
+        }
+    }
+
+    if let Some(language) = &update.language {
+        let mut subtags = language.split('-');
+        let primary = subtags.next().unwrap_or_default();
+        let valid = (2..=3).contains(&primary.len())
+            && primary.chars().all(|c| c.is_ascii_alphabetic())
+            && subtags.all(|t| (1..=8).contains(&t.len()) && t.chars().all(|c| c.is_ascii_alphanumeric()));
+        if !valid {
+            return Err("language must be a BCP 47 language tag, e.g. en-US".into());
+        }
+    }
+
+    if let Some(timezone) = &update.timezone {
+        let valid = !timezone.is_empty()
+            && timezone.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '/' | '_' | '-' | '+'));
+        if !valid {
+            return Err("timezone must be an IANA time zone name, e.g. Europe/Copenhagen".into());
+        }
+    }
+
+    if let Some(region) = &update.region {
+        if region.len() != 2 || !region.chars().all(|c| c.is_ascii_uppercase()) {
+            return Err("region must be an ISO 3166-1 alpha-2 code, e.g. DK".into());
+        }
+    }
+
+    Ok(())
+}
+
+fn apply_update(user: &mut User, update: &UserUpdate) {
+    if let Some(first_name) = &update.first_name {
+        user.first_name = Some(first_name.trim().to_string());
+    }
+    if let Some(last_name) = &update.last_name {
+        user.last_name = Some(last_name.trim().to_string());
+    }
+    if let Some(phone) = &update.phone {
+        user.phone = Some(phone.clone());
+    }
+    if let Some(language) = &update.language {
+        user.language = Some(language.clone());
+    }
+    if let Some(timezone) = &update.timezone {
+        user.timezone = Some(timezone.clone());
+    }
+    if let Some(region) = &update.region {
+        user.region = Some(region.clone());
+    }
+}
+
+/// Replaces the cached user with `previous` unless another update already replaced it.
+fn rollback(previous: User, optimistic: &User) {
+    let unchanged = USER.lock().unwrap().as_ref() == Some(optimistic);
+    if unchanged {
+        set_user(previous);
+    }
+}
+
+/// Updates the profile of the signed-in user.
+///
+/// The cached user is updated immediately and rolled back if Authium rejects the change.
+#[tracing::instrument(name = "authium.update_user", skip_all, err)]
+pub async fn update_user(update: UserUpdate) -> Result<User, String> {
+    validate_update(&update)?;
+
+    let Some(previous) = USER.lock().unwrap().clone() else {
+        return Err("Not logged in".into());
+    };
+    let mut optimistic = previous.clone();
+    apply_update(&mut optimistic, &update);
+    set_user(optimistic.clone());
+
+    if let Err(e) = refresh().await {
+        rollback(previous, &optimistic);
+        return Err(e);
+    }
+
+    let access_token = ACCESS_TOKEN.lock().unwrap().clone().unwrap_or_default();
+    let client = crate::http::client();
+    let _res = client.patch(format!("{}/app/user", daemon_endpoint()))
+        .bearer_auth(access_token)
+        .json(&update)
+        .send_with_retry()
+        .await;
+
+    match _res {
+        Ok(response) if response.status().is_success() => {
+            let Ok(user) = response.json::<User>().await else {
+                rollback(previous, &optimistic);
+                return Err("Failed to parse updated user".into());
+            };
+            set_user(user.clone());
+            Ok(user)
+        }
+        Ok(response) => {
+            let status = response.status();
+            let body: serde_json::Value = response.json().await.unwrap_or_default();
+            rollback(previous, &optimistic);
+            Err(format!("Failed to update user: {} - {}", status, body))
+        }
+        Err(e) => {
+            rollback(previous, &optimistic);
+            Err(format!("Failed to update user: {}", e))
+        }
+    }
+}
+
+/// Uploads a new avatar for the signed-in user and returns the updated user.
+#[tracing::instrument(name = "authium.update_avatar", skip(data), fields(size = data.len()), err)]
+pub async fn update_avatar(data: Vec<u8>, mime_type: String) -> Result<User, String> {
+    if !AVATAR_MIME_TYPES.contains(&mime_type.as_str()) {
+        return Err(format!("Unsupported avatar type: {}", mime_type));
+    }
+    if data.is_empty() || data.len() > MAX_AVATAR_SIZE {
+        return Err(format!("Avatar must be between 1 byte and {} bytes", MAX_AVATAR_SIZE));
+    }
+    if USER.lock().unwrap().is_none() {
+        return Err("Not logged in".into());
+    }
+
+    refresh().await?;
+
+    let part = multipart::Part::bytes(data)
+        .file_name("avatar")
+        .mime_str(&mime_type)
+        .map_err(|e| e.to_string())?;
+    let form = multipart::Form::new().part("avatar", part);
+
+    let access_token = ACCESS_TOKEN.lock().unwrap().clone().unwrap_or_default();
+    let client = crate::http::client();
+    let response = client.put(format!("{}/app/user/avatar", daemon_endpoint()))
+        .bearer_auth(access_token)
+        .multipart(form)
+        .send_with_retry()
+        .await
+        .map_err(|e| format!("Failed to upload avatar: {}", e))?;
+
+    let status = response.status();
+    if !status.is_success() {
+        let body: serde_json::Value = response.json().await.unwrap_or_default();
+        return Err(format!("Failed to upload avatar: {} - {}", status, body));
+    }
+
+    let user = response.json::<User>().await
+        .map_err(|_| "Failed to parse updated user".to_string())?;
+    set_user(user.clone());
+    Ok(user)
+}
+
+#[derive(Deserialize)]
+struct TotpEnrollmentData {
+    enrollment_id: String,
+    otpauth_uri: String,
+    secret: String,
+}
+
+#[derive(Serialize)]
+struct TotpConfirmation<'a> {
+    enrollment_id: &'a str,
+    code: &'a str,
+}
+
+/// Starts adding an authenticator app as a second factor of the signed-in user. The
+/// enrollment takes effect once [`confirm_totp_enrollment`] succeeds.
+#[tracing::instrument(name = "authium.start_totp_enrollment", skip_all, err)]
+pub async fn start_totp_enrollment() -> Result<TotpEnrollment, String> {
+    if USER.lock().unwrap().is_none() {
+        return Err("Not logged in".into());
+    }
+
+    refresh().await?;
+
+    let access_token = ACCESS_TOKEN.lock().unwrap().clone().unwrap_or_default();
+    let client = crate::http::client();
+    let response = client.post(format!("{}/app/user/mfa/totp", daemon_endpoint()))
+        .bearer_auth(access_token)
+        .send_with_retry()
+        .await
+        .map_err(|e| format!("Failed to start TOTP enrollment: {}", e))?;
+
+    let status = response.status();
+    if !status.is_success() {
+        let body: serde_json::Value = response.json().await.unwrap_or_default();
+        return Err(format!("Failed to start TOTP enrollment: {} - {}", status, body));
+    }
+
+    let data = response.json::<TotpEnrollmentData>().await
+        .map_err(|e| format!("Failed to parse TOTP enrollment: {}", e))?;
+    Ok(TotpEnrollment {
+        enrollment_id: data.enrollment_id,
+        otpauth_uri: data.otpauth_uri,
+        secret: data.secret,
+    })
+}
+
+/// Confirms a TOTP enrollment with a code from the authenticator app and returns the updated
+/// user, who then has MFA enabled.
+#[tracing::instrument(name = "authium.confirm_totp_enrollment", skip_all, err)]
+pub async fn confirm_totp_enrollment(enrollment_id: &str, code: &str) -> Result<User, String> {
+    let code = code.trim();
+    if !(6..=8).contains(&code.len()) || !code.chars().all(|c| c.is_ascii_digit()) {
+        return Err("Verification code must be 6 to 8 digits".into());
+    }
+    if USER.lock().unwrap().is_none() {
+        return Err("Not logged in".into());
+    }
+
+    refresh().await?;
+
+    let access_token = ACCESS_TOKEN.lock().unwrap().clone().unwrap_or_default();
+    let client = crate::http::client();
+    let response = client.post(format!("{}/app/user/mfa/totp/confirm", daemon_endpoint()))
+        .bearer_auth(access_token)
+        .json(&TotpConfirmation { enrollment_id, code })
+        .send_with_retry()
+        .await
+        .map_err(|e| format!("Failed to confirm TOTP enrollment: {}", e))?;
+
+    let status = response.status();
+    if matches!(status, StatusCode::BAD_REQUEST | StatusCode::UNPROCESSABLE_ENTITY) {
+        return Err("Invalid verification code".into());
+    }
+    if !status.is_success() {
+        let body: serde_json::Value = response.json().await.unwrap_or_default();
+        return Err(format!("Failed to confirm TOTP enrollment: {} - {}", status, body));
+    }
+
+    let user = response.json::<User>().await
+        .map_err(|_| "Failed to parse updated user".to_string())?;
+    set_user(user.clone());
+    Ok(user)
+}
+
+fn set_user(user: User) {
+    *USER.lock().unwrap() = Some(user.clone());
+    AUTH_STATE.send_replace(AuthState::SignedIn(Box::new(user)));
+}
+
+/// Whether a user is signed in.
+pub fn auth_state() -> AuthState {
+    AUTH_STATE.borrow().clone()
+}
+
+/// Subscribes to sign-ins, sign-outs and changes to the signed in user.
+pub fn subscribe() -> watch::Receiver<AuthState> {
+    AUTH_STATE.subscribe()
+}
 
+pub fn get_user() -> Option<User> {
     let user_lock = USER.lock().unwrap();
     user_lock.clone()
 }
 
-fn clear() {
+/// The current access token, for calling other APIs on behalf of the user.
+pub fn access_token() -> Option<String> {
+    ACCESS_TOKEN.lock().unwrap().clone()
+}
+
+/// Records when the user signed in: at `auth_time` if Authium reported it, now otherwise.
+fn set_auth_time(auth_time: Option<u64>) {
+    *AUTH_TIME.lock().unwrap() = Some(auth_time.map(from_unix_secs).unwrap_or_else(SystemTime::now));
+}
+
+/// Time since the user last signed in interactively, `None` without a session. Refreshing the
+/// session does not reset it, so it tells how recently the user proved their presence.
+pub fn auth_age() -> Option<Duration> {
+    if ACCESS_TOKEN.lock().unwrap().is_none() {
+        return None;
+    }
+    let auth_time = (*AUTH_TIME.lock().unwrap())?;
+    Some(SystemTime::now().duration_since(auth_time).unwrap_or_default())
+}
+
+/// Records the granted scopes and token type reported by Authium. Missing values keep the current ones.
+fn set_token_details(scope: Option<&str>, token_type: Option<String>) {
+    if let Some(scope) = scope {
+        *SCOPES.lock().unwrap() = scope.split_whitespace().map(String::from).collect();
+    }
+    if token_type.is_some() {
+        *TOKEN_TYPE.lock().unwrap() = token_type;
+    }
+}
+
+fn unix_secs(time: SystemTime) -> u64 {
+    time.duration_since(SystemTime::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+fn from_unix_secs(secs: u64) -> SystemTime {
+    SystemTime::UNIX_EPOCH + Duration::from_secs(secs)
+}
+
+/// Returns what is known locally about the current session.
+pub fn get_session_info() -> SessionInfo {
+    if ACCESS_TOKEN.lock().unwrap().is_none() {
+        return SessionInfo::default();
+    }
+
+    SessionInfo {
+        logged_in: true,
+        expires_at: Some(unix_secs(*EXPIRY.lock().unwrap())),
+        issued_at: ISSUED_AT.lock().unwrap().map(unix_secs),
+        auth_time: AUTH_TIME.lock().unwrap().map(unix_secs),
+        scopes: SCOPES.lock().unwrap().clone(),
+        token_type: TOKEN_TYPE.lock().unwrap().clone(),
+        restored: RESTORED.load(Ordering::Relaxed),
+        active: None,
+    }
+}
+
+#[derive(Serialize)]
+struct IntrospectRequest<'a> {
+    token: &'a str,
+    token_type_hint: &'a str,
+}
+
+#[derive(Debug, Deserialize)]
+struct IntrospectionData {
+    active: bool,
+    scope: Option<String>,
+    token_type: Option<String>,
+    exp: Option<u64>,
+    iat: Option<u64>,
+}
+
+/// Asks Authium whether the current access token is still active and merges the answer into
+/// the local [`SessionInfo`]. The app credentials authenticate the introspection request.
+#[tracing::instrument(name = "authium.introspect", skip_all, err)]
+pub async fn introspect_session() -> Result<SessionInfo, String> {
+    let mut info = get_session_info();
+    let Some(token) = ACCESS_TOKEN.lock().unwrap().clone() else {
+        return Ok(info);
+    };
+
+    let client = crate::http::client();
+    let response = client.post(format!("{}/token/introspect", daemon_endpoint()))
+        .client_auth()
+        .form(&IntrospectRequest { token: &token, token_type_hint: "access_token" })
+        .send_with_retry()
+        .await
+        .map_err(|e| format!("Failed to introspect token: {}", e))?;
+
+    let status = response.status();
+    if !status.is_success() {
+        return Err(format!("Failed to introspect token: {}", status));
+    }
+
+    let data = response.json::<IntrospectionData>().await
+        .map_err(|e| format!("Failed to parse introspection response: {}", e))?;
+
+    info.active = Some(data.active);
+    if data.active {
+        if let Some(scope) = data.scope {
+            info.scopes = scope.split_whitespace().map(String::from).collect();
+        }
+        info.token_type = data.token_type.or(info.token_type);
+        info.expires_at = data.exp.or(info.expires_at);
+        info.issued_at = data.iat.or(info.issued_at);
+    }
+
+    Ok(info)
+}
+
+/// Ends the session in memory and on disk, holding the session lock so another process
+/// cannot adopt the session while it is removed.
+async fn clear() {
+    let _lock = lock_storage().await
+        .inspect_err(|e| tracing::warn!(error = %e, "clearing the session without the session lock"));
+    clear_locked();
+}
+
+/// Like [`clear`]. Callers hold the session lock.
+fn clear_locked() {
     *USER.lock().unwrap() = None;
     *ACCESS_TOKEN.lock().unwrap() = None;
     *REFRESH_TOKEN.lock().unwrap() = None;
     *EXPIRY.lock().unwrap() = SystemTime::now();
+    *ISSUED_AT.lock().unwrap() = None;
+    *AUTH_TIME.lock().unwrap() = None;
+    SCOPES.lock().unwrap().clear();
+    *TOKEN_TYPE.lock().unwrap() = None;
+    SESSION_ID.lock().unwrap().clear();
+    RESTORED.store(false, Ordering::Relaxed);
     clear_user_data();
+    AUTH_STATE.send_if_modified(|state| std::mem::take(state) != AuthState::SignedOut);
+}
+
+/// A token revocation that still has to reach Authium. Revocations that fail
+/// because of the network are persisted and retried on the next launch.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub(crate) enum PendingRevocation {
+    Token { token: String, token_type_hint: String },
+    AllSessions { refresh_token: String },
+}
+
+impl std::fmt::Debug for PendingRevocation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Token { token, token_type_hint } => f.debug_struct("Token")
+                .field("token", &Redacted(token))
+                .field("token_type_hint", token_type_hint)
+                .finish(),
+            Self::AllSessions { refresh_token } => f.debug_struct("AllSessions")
+                .field("refresh_token", &Redacted(refresh_token))
+                .finish(),
+        }
+    }
 }
 
-pub fn logout() {
-    let token = REFRESH_TOKEN.lock().unwrap().clone();
-    if let Some(token) = token {
-        let client = Client::new();
-        let _ = client
-            .post(format!("{}/user/logout", DAEMON_ENDPOINT))
-            .bearer_auth(token)
-            .send();
+#[derive(Serialize)]
+struct RevokeRequest<'a> {
+    token: &'a str,
+    token_type_hint: &'a str,
+}
+
+#[derive(Debug, Serialize)]
+struct LogoutRequest {
+    all_sessions: bool,
+}
+
+/// Sends a single revocation. `Err` means the request should be retried later,
+/// client errors are considered final since retrying would not change the outcome.
+#[tracing::instrument(name = "authium.revoke", skip(client), err)]
+async fn revoke(client: &Client, revocation: &PendingRevocation) -> Result<(), String> {
+    let request = match revocation {
+        PendingRevocation::Token { token, token_type_hint } => client
+            .post(format!("{}/token/revoke", daemon_endpoint()))
+            .client_auth()
+            .form(&RevokeRequest { token, token_type_hint }),
+        PendingRevocation::AllSessions { refresh_token } => client
+            .post(format!("{}/user/logout", daemon_endpoint()))
+            .bearer_auth(refresh_token)
+            .json(&LogoutRequest { all_sessions: true }),
+    };
+
+    let response = request.send_with_retry().await.map_err(|e| e.to_string())?;
+    let status = response.status();
+    if status.is_server_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS {
+        return Err(format!("Failed to revoke token: {}", status));
+    }
+    if !status.is_success() {
+        tracing::warn!(%status, "Authium rejected token revocation");
+    }
+
+    Ok(())
+}
+
+/// Sends the given revocations in order and queues the ones that failed.
+async fn revoke_all(revocations: Vec<PendingRevocation>) {
+    let client = crate::http::client();
+    let mut failed = Vec::new();
+    for revocation in revocations {
+        if revoke(&client, &revocation).await.is_err() {
+            failed.push(revocation);
+        }
+    }
+
+    if failed.is_empty() {
+        return;
+    }
+
+    let _lock = lock_storage().await
+        .inspect_err(|e| tracing::warn!(error = %e, "queueing token revocations without the session lock"));
+    let mut queued = load_pending_revocations();
+    tracing::info!(count = failed.len(), "queueing token revocations for the next launch");
+    queued.extend(failed);
+    if let Err(e) = save_pending_revocations(&queued) {
+        tracing::error!(error = %e, "failed to queue token revocations");
+    }
+}
+
+/// Retries the revocations queued by a previous logout that could not reach Authium.
+pub(crate) async fn retry_pending_revocations() {
+    let pending = {
+        let _lock = lock_storage().await
+            .inspect_err(|e| tracing::warn!(error = %e, "taking queued token revocations without the session lock"));
+        let pending = load_pending_revocations();
+        if pending.is_empty() {
+            return;
+        }
+        if let Err(e) = save_pending_revocations(&[]) {
+            tracing::error!(error = %e, "failed to clear queued token revocations");
+        }
+        pending
+    };
+
+    tracing::info!(count = pending.len(), "retrying queued token revocations");
+    revoke_all(pending).await;
+}
+
+/// Logs the user out and revokes both the access and refresh token.
+///
+/// Local state is cleared before contacting Authium, so the user is logged out
+/// even when the network is unavailable. If `everywhere` is set, every other
+/// session of the user is ended as well.
+#[tracing::instrument(name = "authium.logout")]
+pub async fn logout(everywhere: bool) {
+    let access_token = ACCESS_TOKEN.lock().unwrap().clone();
+    let refresh_token = REFRESH_TOKEN.lock().unwrap().clone();
+    clear().await;
+
+    let mut revocations = Vec::new();
+    if let Some(token) = refresh_token {
+        // must run before the refresh token itself is revoked
+        if everywhere {
+            revocations.push(PendingRevocation::AllSessions { refresh_token: token.clone() });
+        }
+        revocations.push(PendingRevocation::Token { token, token_type_hint: "refresh_token".into() });
+    }
+    if let Some(token) = access_token {
+        revocations.push(PendingRevocation::Token { token, token_type_hint: "access_token".into() });
     }
 
-    clear();
+    revoke_all(revocations).await;
 }
 
 pub fn is_logged_in() -> bool {
@@ -0,0 +1,58 @@
+//! Keeps the TypeScript definitions in `guest-js/bindings` in sync with the Rust models.
+//!
+//! The models are compiled into this test, where `cfg(test)` derives their `TS` impls, so a
+//! plain `cargo test` fails once they drift. Regenerate them with
+//! `UPDATE_BINDINGS=1 cargo test --test bindings`.
+
+use std::{fs, path::Path};
+
+#[allow(dead_code)]
+#[path = "../src/models.rs"]
+mod models;
+#[allow(dead_code)]
+#[path = "../src/redact.rs"]
+mod redact;
+
+use models::{
+    AuthState, ConfirmTotpPayload, DeviceCode, LogoutPayload, MfaFactor, ReauthenticatePayload, RefreshPayload, SessionInfo,
+    SessionInfoPayload, SignInPayload, TotpEnrollment, User, UserUpdate,
+};
+use ts_rs::TS;
+
+fn check<T: TS + 'static>() {
+    let generated = T::export_to_string().expect("failed to generate bindings");
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("guest-js/bindings")
+        .join(format!("{}.ts", T::ident()));
+
+    if std::env::var_os("UPDATE_BINDINGS").is_some() {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, generated).unwrap();
+        return;
+    }
+
+    let committed = fs::read_to_string(&path).unwrap_or_default();
+    assert_eq!(
+        committed,
+        generated,
+        "{} is out of date, run `UPDATE_BINDINGS=1 cargo test --test bindings`",
+        path.display()
+    );
+}
+
+#[test]
+fn bindings_match_models() {
+    check::<User>();
+    check::<UserUpdate>();
+    check::<SignInPayload>();
+    check::<LogoutPayload>();
+    check::<RefreshPayload>();
+    check::<AuthState>();
+    check::<ReauthenticatePayload>();
+    check::<SessionInfoPayload>();
+    check::<SessionInfo>();
+    check::<DeviceCode>();
+    check::<MfaFactor>();
+    check::<TotpEnrollment>();
+    check::<ConfirmTotpPayload>();
+}
@@ -0,0 +1,119 @@
+//! Plugin setup with incomplete and merged configuration, and the window allow-list.
+
+use tauri::{
+    ipc::{CallbackFn, InvokeBody},
+    test::{get_ipc_response, mock_builder, mock_context, noop_assets, INVOKE_KEY},
+    utils::acl::ExecutionContext,
+    webview::InvokeRequest,
+    WebviewUrl, WebviewWindow, WebviewWindowBuilder,
+};
+use tauri_plugin_authium::{AuthiumConfig, Builder, Error};
+
+#[test]
+fn missing_app_id_fails_setup() {
+    let result = mock_builder()
+        .plugin(Builder::new().api_key("key").build())
+        .build(mock_context(noop_assets()));
+
+    let error = result.err().expect("setup should fail").to_string();
+    assert!(error.contains("appId"), "{}", error);
+}
+
+#[test]
+fn code_values_override_the_config_file() {
+    let file = AuthiumConfig {
+        app_id: Some("file-app".into()),
+        port: Some(1234),
+        ..Default::default()
+    };
+    let code = AuthiumConfig { app_id: Some("code-app".into()), ..Default::default() };
+
+    let merged = file.merge(code);
+    assert_eq!(merged.app_id.as_deref(), Some("code-app"));
+    assert_eq!(merged.port, Some(1234));
+    assert!(matches!(merged.validate(), Err(Error::MissingConfig("apiKey"))));
+}
+
+#[test]
+fn profile_and_environment_override_the_config_file() {
+    let staging = AuthiumConfig {
+        api_url: Some("https://api.staging.example.com/v1".into()),
+        auth_url: Some("https://auth.staging.example.com".into()),
+        ..Default::default()
+    };
+    let file = AuthiumConfig {
+        app_id: Some("file-app".into()),
+        api_url: Some("https://api.example.com/v1".into()),
+        profile: Some("prod".into()),
+        profiles: [("staging".to_string(), staging), ("prod".to_string(), AuthiumConfig::default())].into(),
+        ..Default::default()
+    };
+    let code = AuthiumConfig { api_key: Some("code-key".into()), ..Default::default() };
+    let env = AuthiumConfig::from_vars(|name| match name {
+        "AUTHIUM_PROFILE" => Some("staging".into()),
+        "AUTHIUM_APP_ID" => Some("env-app".into()),
+        "AUTHIUM_PORT" => Some("7000".into()),
+        "AUTHIUM_PROXY" => Some("".into()),
+        _ => None,
+    })
+    .unwrap();
+
+    let resolved = file.resolve(code, env).unwrap();
+    assert_eq!(resolved.profile.as_deref(), Some("staging"));
+    assert_eq!(resolved.api_url.as_deref(), Some("https://api.staging.example.com/v1"));
+    assert_eq!(resolved.auth_url.as_deref(), Some("https://auth.staging.example.com"));
+    assert_eq!(resolved.app_id.as_deref(), Some("env-app"));
+    assert_eq!(resolved.api_key.as_deref(), Some("code-key"));
+    assert_eq!(resolved.port, Some(7000));
+    assert_eq!(resolved.proxy, None);
+}
+
+#[test]
+fn unknown_profile_and_invalid_variables_are_rejected() {
+    let code = AuthiumConfig { profile: Some("qa".into()), ..Default::default() };
+    let result = AuthiumConfig::default().resolve(code, AuthiumConfig::default());
+    assert!(matches!(result, Err(Error::UnknownProfile(name)) if name == "qa"));
+
+    let result = AuthiumConfig::from_vars(|name| (name == "AUTHIUM_PORT").then(|| "http".into()));
+    assert!(matches!(result, Err(Error::InvalidConfig { name: "AUTHIUM_PORT", .. })));
+}
+
+#[test]
+fn commands_are_limited_to_allowed_windows() {
+    let mut context = mock_context(noop_assets());
+    context
+        .runtime_authority_mut()
+        .__allow_command("plugin:authium|is_logged_in".into(), ExecutionContext::Local);
+    let app = mock_builder()
+        .plugin(
+            Builder::new()
+                .api_key("key")
+                .app_id("app")
+                .port(0)
+                .store(std::env::temp_dir().join(format!("authium-config-test-{}", std::process::id())))
+                .allow_windows("is_logged_in", ["main"])
+                .build(),
+        )
+        .build(context)
+        .expect("failed to build app");
+
+    let is_logged_in = |window: &WebviewWindow<_>| {
+        get_ipc_response(
+            window,
+            InvokeRequest {
+                cmd: "plugin:authium|is_logged_in".into(),
+                callback: CallbackFn(0),
+                error: CallbackFn(1),
+                url: "tauri://localhost".parse().unwrap(),
+                body: InvokeBody::default(),
+                headers: Default::default(),
+                invoke_key: INVOKE_KEY.to_string(),
+            },
+        )
+    };
+    let window = |label: &str| WebviewWindowBuilder::new(&app, label, WebviewUrl::default()).build().unwrap();
+
+    assert!(is_logged_in(&window("main")).is_ok());
+    assert!(is_logged_in(&window("settings")).is_err());
+    assert!(is_logged_in(&window("authium-auth-cb-signin")).is_err());
+}